@@ -21,6 +21,10 @@ rm -rf ${PREFIX}
 mkdir -p ${PREFIX}
 mkdir -p ${PREFIX}/lib
 cp target/release/fuzzer ${PREFIX}
+cp target/release/libfuzzer_loader ${PREFIX}
+cp target/release/showmap ${PREFIX}
+cp target/release/cmin ${PREFIX}
+cp target/release/tmin ${PREFIX}
 cp target/release/*.a ${PREFIX}/lib
 
 cd llvm_mode
@@ -19,10 +19,32 @@ pub const BRANCHES_SIZE: usize = 1 << MAP_SIZE_POW2;
 
 // executor.rs:
 pub const TMOUT_SKIP: usize = 3;
-pub const TIME_LIMIT: u64 = 1;
+pub const TIME_LIMIT: u64 = 1000; // ms
 pub const MEM_LIMIT: u64 = 200; // MB
-pub const TIME_LIMIT_TRACK: u64 = 20;
+/// Upper bound of the track timeout, which scales from the fast one.
+pub const TIME_LIMIT_TRACK: u64 = 20000; // ms
+pub const TIME_LIMIT_TRACK_MIN: u64 = 1000; // ms
+pub const TIME_LIMIT_TRACK_RATIO: u64 = 20;
+pub const TIME_LIMIT_SAN_MIN: u64 = 100; // ms
+pub const TIME_LIMIT_SAN_RATIO: u64 = 3;
+/// A timeout is a hang only if the sanitized program also runs past this, and
+/// then gets SIGABRT so that the sanitizer prints where it is stuck.
+pub const TIME_LIMIT_HANG_MIN: u64 = 1000; // ms
+pub const TIME_LIMIT_HANG_RATIO: u64 = 5;
+/// How long the sanitizer has to print the stack after SIGABRT.
+pub const HANG_REPORT_WAIT: u64 = 1000; // ms
+/// Executions to shrink a confirmed hang.
+pub const HANG_MIN_EXECS: usize = 64;
+/// Executions to minimize the first crash of a new bucket, with `--minimize_crashes`.
+pub const CRASH_MIN_EXECS: usize = 1024;
+/// `-T auto`: the timeout is the slowest seed's execution time times the ratio,
+/// clamped to [MIN, MAX]. The dry run itself uses MAX.
+pub const TIME_LIMIT_CAL_RATIO: u64 = 5;
+pub const TIME_LIMIT_CAL_MIN: u64 = 20; // ms
+pub const TIME_LIMIT_CAL_MAX: u64 = 1000; // ms
 pub const MEM_LIMIT_TRACK: u64 = 0;
+/// `pids.max` of the cgroup of each executor, with `--cgroup`.
+pub const CGROUP_PIDS_MAX: u64 = 256;
 /// Fuzz time for one condition
 pub const LONG_FUZZ_TIME: usize = 16;
 /// Fuzz time in one satte
@@ -49,6 +71,21 @@ pub const RANDOM_LEN_NUM: usize = 30;
 pub const MAX_HAVOC_FLIP_TIMES: usize = 45; // for all bytes
 pub const MAX_SPLICE_TIMES: usize = 45;
 
+// -- crash buckets
+// Top stack frames that identify a crash, 0 for all of them.
+pub const CRASH_BUCKET_FRAMES: usize = 3;
+/// Reruns of each saved crash in both the fast and the sanitized programs.
+pub const CRASH_REPRO_RUNS: usize = 5;
+
+/// What identifies a stack frame in a crash bucket.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CrashFrameKey {
+    /// `(module+offset)`, or the source location if the report is symbolized.
+    ModuleOffset,
+    /// The function name, which needs the sanitizers to symbolize the report.
+    Function,
+}
+
 #[derive(Debug)]
 pub struct FuzzerConfig {
     /// Use AFL as a side help.
@@ -70,6 +107,18 @@ pub struct FuzzerConfig {
 
     belong: bool,
     order: bool,
+
+    /// Top stack frames in a crash bucket, 0 for all.
+    crash_frames: usize,
+    /// Leave out the frames in sanitizer runtimes and libc from crash buckets.
+    skip_runtime_frames: bool,
+    crash_frame_key: CrashFrameKey,
+    /// Write a JSON file of metadata for each input in the queue.
+    queue_metadata: bool,
+    /// Minimize the first crash of each new bucket.
+    minimize_crashes: bool,
+    /// Let ASan report leaks, which LSan then aborts on.
+    detect_leaks: bool,
 }
 
 pub static CONFIG: OnceCell<FuzzerConfig> = OnceCell::new();
@@ -97,6 +146,12 @@ impl Default for FuzzerConfig {
             max_priority: std::u16::MAX,
             belong: false,
             order: true,
+            crash_frames: CRASH_BUCKET_FRAMES,
+            skip_runtime_frames: true,
+            crash_frame_key: CrashFrameKey::ModuleOffset,
+            queue_metadata: false,
+            minimize_crashes: false,
+            detect_leaks: false,
         }
     }
 }
@@ -178,4 +233,46 @@ impl FuzzerConfig {
     pub fn order(&self) -> bool {
         self.order
     }
+    pub fn set_crash_frames<'a>(&'a mut self, crash_frames: usize) -> &'a mut Self {
+        self.crash_frames = crash_frames;
+        self
+    }
+    pub fn crash_frames(&self) -> usize {
+        self.crash_frames
+    }
+    pub fn set_skip_runtime_frames<'a>(&'a mut self, skip_runtime_frames: bool) -> &'a mut Self {
+        self.skip_runtime_frames = skip_runtime_frames;
+        self
+    }
+    pub fn skip_runtime_frames(&self) -> bool {
+        self.skip_runtime_frames
+    }
+    pub fn set_crash_frame_key<'a>(&'a mut self, crash_frame_key: CrashFrameKey) -> &'a mut Self {
+        self.crash_frame_key = crash_frame_key;
+        self
+    }
+    pub fn crash_frame_key(&self) -> CrashFrameKey {
+        self.crash_frame_key
+    }
+    pub fn set_queue_metadata<'a>(&'a mut self, queue_metadata: bool) -> &'a mut Self {
+        self.queue_metadata = queue_metadata;
+        self
+    }
+    pub fn queue_metadata(&self) -> bool {
+        self.queue_metadata
+    }
+    pub fn set_minimize_crashes<'a>(&'a mut self, minimize_crashes: bool) -> &'a mut Self {
+        self.minimize_crashes = minimize_crashes;
+        self
+    }
+    pub fn minimize_crashes(&self) -> bool {
+        self.minimize_crashes
+    }
+    pub fn set_detect_leaks<'a>(&'a mut self, detect_leaks: bool) -> &'a mut Self {
+        self.detect_leaks = detect_leaks;
+        self
+    }
+    pub fn detect_leaks(&self) -> bool {
+        self.detect_leaks
+    }
 }
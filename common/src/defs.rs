@@ -7,23 +7,45 @@ pub static ANGORA_BIN_DIR: &str = "ANGORA_BIN_DIR";
 pub static TRACK_OUTPUT_VAR: &str = "ANGORA_TRACK_OUTPUT";
 pub static COND_STMT_ENV_VAR: &str = "ANGORA_COND_STMT_SHM_ID";
 pub static BRANCHES_SHM_ENV_VAR: &str = "ANGORA_BRANCHES_SHM_ID";
+pub static INPUT_SHM_ENV_VAR: &str = "ANGORA_INPUT_SHM_ID";
 pub static LD_LIBRARY_PATH_VAR: &str = "LD_LIBRARY_PATH";
 pub static ASAN_OPTIONS_VAR: &str = "ASAN_OPTIONS";
 pub static MSAN_OPTIONS_VAR: &str = "MSAN_OPTIONS";
+pub static UBSAN_OPTIONS_VAR: &str = "UBSAN_OPTIONS";
 pub static ASAN_OPTIONS_CONTENT: &str =
     "abort_on_error=1:detect_leaks=0:symbolize=0:allocator_may_return_null=1";
 pub const MSAN_ERROR_CODE: i32 = 86;
 pub static MSAN_OPTIONS_CONTENT: &str =
     "exit_code=86:symbolize=0:abort_on_error=1:allocator_may_return_null=1:msan_track_origins=0";
+// UBSan only prints the first error of each kind and goes on unless told to stop.
+pub static UBSAN_OPTIONS_CONTENT: &str =
+    "halt_on_error=1:abort_on_error=1:print_stacktrace=1:symbolize=0";
 
 // depot.rs
 pub static CRASHES_DIR: &str = "crashes";
+pub static CRASH_INDEX_FILE: &str = "index.json";
+pub static UNCONFIRMED_CRASHES_DIR: &str = "crashes_unconfirmed";
 pub static HANGS_DIR: &str = "hangs";
+pub static OOMS_DIR: &str = "ooms";
 pub static INPUTS_DIR: &str = "queue";
+pub static QUEUE_META_DIR: &str = ".meta";
+pub static BRANCHES_DIR: &str = "branches";
+pub static KNOWN_CRASHES_FILE: &str = "crashes.json";
+pub static KNOWN_HANGS_FILE: &str = "hangs.json";
 
 // forksrv.rs
 pub static ENABLE_FORKSRV: &str = "ANGORA_ENABLE_FORKSRV";
 pub static FORKSRV_SOCKET_PATH_VAR: &str = "ANGORA_FORKSRV_SOCKET_PATH";
+pub static SAN_STDERR_VAR: &str = "ANGORA_SAN_STDERR";
+
+// libfuzzer_executor.rs
+pub static WORKER_SOCKET_PATH_VAR: &str = "ANGORA_WORKER_SOCKET_PATH";
+
+// check_dep.rs
+pub static PERSIST_SIG: &str = "##SIG_ANGORA_PERSISTENT##";
+pub static SHM_INPUT_SIG: &str = "##SIG_ANGORA_SHM_INPUT##";
+pub static TRACK_FORKSRV_SIG: &str = "##SIG_ANGORA_TRACK_FORKSRV##";
+pub static SAN_FORKSRV_SIG: &str = "##SIG_ANGORA_SAN_FORKSRV##";
 
 // command.rs
 pub static ANGORA_DIR_NAME: &str = "angora";
@@ -31,6 +53,8 @@ pub static ANGORA_LOG_FILE: &str = "angora.log";
 pub static COND_QUEUE_FILE: &str = "cond_queue.csv";
 pub static CONFIG_FILE: &str = "config.txt";
 pub static CHART_STAT_FILE: &str = "chart_stat.json";
+pub static COVERAGE_LCOV_FILE: &str = "coverage.lcov";
+pub static CHECKPOINT_FILE: &str = "checkpoint.json";
 
 // tmpfs.rs
 pub static PERSIST_TRACK_FILES: &str = "ANGORA_DISABLE_TMPFS";
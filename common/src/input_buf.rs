@@ -0,0 +1,40 @@
+use crate::config::MAX_INPUT_LEN;
+
+// Test case handed to the fast binary through shared memory,
+// instead of writing it to `cur_input`.
+#[repr(C)] // It should be repr C since we will used it in shared memory
+pub struct InputBuf {
+    pub len: u32,
+    pub data: [u8; MAX_INPUT_LEN],
+}
+
+impl InputBuf {
+    pub fn as_slice(&self) -> &[u8] {
+        let len = std::cmp::min(self.len as usize, MAX_INPUT_LEN);
+        &self.data[..len]
+    }
+
+    pub fn set(&mut self, buf: &[u8]) -> usize {
+        let len = std::cmp::min(buf.len(), MAX_INPUT_LEN);
+        self.data[..len].copy_from_slice(&buf[..len]);
+        self.len = len as u32;
+        len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shm::SHM;
+
+    #[test]
+    fn test_set_input() {
+        let mut input = SHM::<InputBuf>::new();
+        assert_eq!(3, input.set(&[1, 2, 3]));
+        assert_eq!(&[1, 2, 3], input.as_slice());
+
+        let long = vec![7_u8; MAX_INPUT_LEN + 1];
+        assert_eq!(MAX_INPUT_LEN, input.set(&long));
+        assert_eq!(MAX_INPUT_LEN, input.as_slice().len());
+    }
+}
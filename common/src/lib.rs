@@ -1,6 +1,7 @@
 pub mod cond_stmt_base;
 pub mod config;
 pub mod defs;
+pub mod input_buf;
 pub mod log_data;
 pub mod shm;
 pub mod tag;
@@ -0,0 +1,390 @@
+//! Shrinks a corpus to the smallest set of inputs that covers the same branches
+//! of the fast binary, with the same hit count buckets:
+//!     cmin -i INPUT [-i INPUT ...] -o OUTPUT [-j JOBS] -- /path/to/fast.bin ARGS @@
+//! Each branch and bucket is covered by the smallest input that has it, and the
+//! fastest of those of the same size. The rarest are picked first, then the others
+//! that these do not cover yet. Inputs that crash or time out are left out.
+use angora::{
+    branches::{Branches, GlobalBranches},
+    cond_stmt::ShmConds,
+    executor::{pipe_fd::PipeFd, Forksrv, StatusType},
+    parse_time_limit,
+};
+use angora_common::{config, defs};
+use clap::*;
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::{Path, PathBuf},
+    process::{self, Command},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Instant,
+};
+
+// A branch of the table and the bit of its hit count bucket.
+type Tuple = (usize, u8);
+
+struct Runner {
+    branches: Branches,
+    // Keeps the shared memory of the conditions alive.
+    _t_conds: ShmConds,
+    fd: PipeFd,
+    is_stdin: bool,
+    socket_path: String,
+    target: (String, Vec<String>),
+    envs: HashMap<String, String>,
+    time_limit: u64,
+    mem_limit: u64,
+    forksrv: Option<Forksrv>,
+}
+
+impl Runner {
+    fn new(pargs: &[String], tmp_dir: &Path, id: usize, time_limit: u64, mem_limit: u64) -> Self {
+        let shm_id = format!("angora_cmin_{}_{}-shm", process::id(), id);
+        let global_branches = Arc::new(GlobalBranches::new());
+        let branches = Branches::new(global_branches, &shm_id);
+        let t_conds = ShmConds::new();
+
+        let mut envs = HashMap::new();
+        envs.insert(
+            defs::ASAN_OPTIONS_VAR.to_string(),
+            defs::ASAN_OPTIONS_CONTENT.to_string(),
+        );
+        envs.insert(
+            defs::MSAN_OPTIONS_VAR.to_string(),
+            defs::MSAN_OPTIONS_CONTENT.to_string(),
+        );
+        envs.insert(
+            defs::UBSAN_OPTIONS_VAR.to_string(),
+            defs::UBSAN_OPTIONS_CONTENT.to_string(),
+        );
+        envs.insert(defs::BRANCHES_SHM_ENV_VAR.to_string(), shm_id);
+        envs.insert(
+            defs::COND_STMT_ENV_VAR.to_string(),
+            t_conds.get_id().to_string(),
+        );
+        let clang_lib = Command::new("llvm-config")
+            .arg("--libdir")
+            .output()
+            .expect("Can't find llvm-config")
+            .stdout;
+        let clang_lib = String::from_utf8(clang_lib).unwrap();
+        let ld_library = "$LD_LIBRARY_PATH:".to_string() + clang_lib.trim();
+        envs.insert(defs::LD_LIBRARY_PATH_VAR.to_string(), ld_library);
+
+        let input_file = tmp_dir
+            .join(format!("cur_input_{}", id))
+            .to_str()
+            .unwrap()
+            .to_owned();
+        let socket_path = tmp_dir
+            .join(format!("socket_{}", id))
+            .to_str()
+            .unwrap()
+            .to_owned();
+        let fd = PipeFd::new(&input_file);
+        let mut is_stdin = true;
+        let args = pargs[1..]
+            .iter()
+            .map(|arg| {
+                if arg == "@@" {
+                    is_stdin = false;
+                    input_file.clone()
+                } else {
+                    arg.clone()
+                }
+            })
+            .collect();
+
+        let mut runner = Self {
+            branches,
+            _t_conds: t_conds,
+            fd,
+            is_stdin,
+            socket_path,
+            target: (pargs[0].clone(), args),
+            envs,
+            time_limit,
+            mem_limit,
+            forksrv: None,
+        };
+        runner.rebind_forksrv();
+        runner
+    }
+
+    fn rebind_forksrv(&mut self) {
+        // The old one removes the socket that the new one binds.
+        self.forksrv = None;
+        self.forksrv = Some(Forksrv::new(
+            &self.socket_path,
+            &self.target,
+            &self.envs,
+            self.fd.as_raw_fd(),
+            self.is_stdin,
+            false,
+            self.time_limit,
+            self.mem_limit,
+            None,
+        ));
+        self.branches.resize().unwrap();
+    }
+
+    fn run(&mut self, buf: &Vec<u8>) -> (StatusType, Vec<Tuple>) {
+        self.fd.write_buf(buf);
+        let mut status = self.run_forksrv();
+        if status == StatusType::Error {
+            // The input is not to blame for a dead forkserver.
+            self.rebind_forksrv();
+            status = self.run_forksrv();
+        }
+        (status, self.branches.get_path())
+    }
+
+    fn run_forksrv(&mut self) -> StatusType {
+        if self.is_stdin {
+            self.fd.rewind();
+        }
+        self.branches.clear_trace();
+        self.forksrv.as_mut().unwrap().run().status
+    }
+}
+
+struct Sample {
+    path: PathBuf,
+    len: usize,
+    // In us.
+    exec_time: u128,
+    tuples: Vec<Tuple>,
+}
+
+fn list_inputs(dirs: &[&str]) -> Vec<PathBuf> {
+    let mut inputs = vec![];
+    for dir in dirs {
+        let dir = Path::new(dir);
+        if !dir.is_dir() {
+            inputs.push(dir.to_path_buf());
+            continue;
+        }
+        let mut files: Vec<PathBuf> = fs::read_dir(dir)
+            .expect("Could not read the input directory")
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file())
+            .collect();
+        files.sort();
+        inputs.extend(files);
+    }
+    inputs
+}
+
+/// Run the inputs on `jobs` forkservers, each in a thread taking the next input
+/// that has not been run. Only inputs that exit normally are sampled.
+fn run_inputs(
+    inputs: Vec<PathBuf>,
+    pargs: Vec<String>,
+    tmp_dir: &Path,
+    jobs: usize,
+    time_limit: u64,
+    mem_limit: u64,
+) -> Vec<Sample> {
+    let inputs = Arc::new(inputs);
+    let pargs = Arc::new(pargs);
+    let next = Arc::new(AtomicUsize::new(0));
+    let mut handles = vec![];
+    for id in 0..jobs {
+        let inputs = inputs.clone();
+        let pargs = pargs.clone();
+        let next = next.clone();
+        let tmp_dir = tmp_dir.to_path_buf();
+        handles.push(thread::spawn(move || {
+            let mut runner = Runner::new(&pargs, &tmp_dir, id, time_limit, mem_limit);
+            let mut samples = vec![];
+            loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let path = match inputs.get(i) {
+                    Some(path) => path,
+                    None => break,
+                };
+                let buf = match fs::read(path) {
+                    Ok(buf) => buf,
+                    Err(e) => {
+                        eprintln!("Could not read {:?}: {:?}", path, e);
+                        continue;
+                    },
+                };
+                let t = Instant::now();
+                let (status, tuples) = runner.run(&buf);
+                let exec_time = t.elapsed().as_micros();
+                if status != StatusType::Normal {
+                    eprintln!("{}: {:?}, left out", path.display(), status);
+                    continue;
+                }
+                samples.push(Sample {
+                    path: path.clone(),
+                    len: buf.len(),
+                    exec_time,
+                    tuples,
+                });
+            }
+            samples
+        }));
+    }
+    let mut samples = vec![];
+    for handle in handles {
+        match handle.join() {
+            Ok(s) => samples.extend(s),
+            Err(_) => {
+                eprintln!("Error happened in a forkserver thread!");
+                process::exit(1);
+            },
+        }
+    }
+    samples.sort_by(|a, b| a.path.cmp(&b.path));
+    samples
+}
+
+/// The samples to keep, as indices into `samples`.
+fn pick_covering_set(samples: &[Sample]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..samples.len()).collect();
+    order.sort_by_key(|&i| (samples[i].len, samples[i].exec_time));
+    // The best sample of each tuple, the first of the order, and how many have it.
+    let mut best: HashMap<Tuple, (usize, usize)> = HashMap::new();
+    for &i in &order {
+        for &t in &samples[i].tuples {
+            best.entry(t).or_insert((i, 0)).1 += 1;
+        }
+    }
+
+    let mut tuples: Vec<(Tuple, usize, usize)> = best
+        .into_iter()
+        .map(|(t, (i, count))| (t, i, count))
+        .collect();
+    tuples.sort_by_key(|&(t, _, count)| (count, t));
+    let mut covered = HashSet::new();
+    let mut picked = vec![];
+    for (t, i, _) in tuples {
+        if covered.contains(&t) {
+            continue;
+        }
+        picked.push(i);
+        covered.extend(samples[i].tuples.iter().cloned());
+    }
+    picked.sort();
+    picked
+}
+
+fn main() {
+    let matches = App::new("cmin")
+        .about("Shrink corpora to the smallest set of inputs with the same coverage in the fast binary.")
+        .arg(Arg::with_name("input")
+            .short("i")
+            .long("input")
+            .value_name("INPUT")
+            .help("A directory of inputs, or an input file. Can be given more than once")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .required(true))
+        .arg(Arg::with_name("output")
+            .short("o")
+            .long("output")
+            .value_name("OUTPUT")
+            .help("Directory to copy the inputs that are kept to. It must not exist")
+            .takes_value(true)
+            .required(true))
+        .arg(Arg::with_name("jobs")
+            .short("j")
+            .long("jobs")
+            .value_name("JOB")
+            .help("Number of forkservers running the inputs in parallel, default is the number of cpus")
+            .takes_value(true))
+        .arg(Arg::with_name("time_limit")
+            .short("T")
+            .long("time_limit")
+            .value_name("TIME")
+            .help("Time limit in seconds (1) or milliseconds (50ms)")
+            .takes_value(true)
+            .validator(|t| {
+                parse_time_limit(&t)?
+                    .map(|_| ())
+                    .ok_or_else(|| "cmin cannot calibrate the time limit".to_owned())
+            }))
+        .arg(Arg::with_name("memory_limit")
+            .short("M")
+            .long("memory_limit")
+            .value_name("MEM")
+            .help("Memory limit in MB")
+            .takes_value(true))
+        .arg(Arg::with_name("pargs")
+            .help("Targeted program (USE_FAST) and arguments. Any \"@@\" will be substituted with the input filename.")
+            .required(true)
+            .multiple(true)
+            .allow_hyphen_values(true)
+            .last(true)
+            .index(1))
+        .get_matches();
+
+    let inputs = list_inputs(&matches.values_of("input").unwrap().collect::<Vec<_>>());
+    let output = Path::new(matches.value_of("output").unwrap());
+    let jobs = value_t!(matches, "jobs", usize)
+        .unwrap_or_else(|_| num_cpus::get())
+        .max(1);
+    let time_limit = matches
+        .value_of("time_limit")
+        .and_then(|t| parse_time_limit(t).unwrap())
+        .unwrap_or(config::TIME_LIMIT);
+    let mem_limit = value_t!(matches, "memory_limit", u64).unwrap_or(config::MEM_LIMIT);
+    fs::create_dir(output).expect("Output directory has existed!");
+
+    let tmp_dir = env::temp_dir().join(format!("angora_cmin_{}", process::id()));
+    fs::create_dir_all(&tmp_dir).expect("Could not create the temporary directory");
+    let num_inputs = inputs.len();
+    let samples = run_inputs(
+        inputs,
+        matches.values_of_lossy("pargs").unwrap(),
+        &tmp_dir,
+        jobs,
+        time_limit,
+        mem_limit,
+    );
+    let _ = fs::remove_dir_all(&tmp_dir);
+
+    let picked = pick_covering_set(&samples);
+    let mut names = HashSet::new();
+    let mut total_len = 0;
+    for &i in &picked {
+        let sample = &samples[i];
+        // Inputs from different directories may have the same name.
+        let mut name = sample
+            .path
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .into_owned();
+        if !names.insert(name.clone()) {
+            name = format!("{},{}", name, i);
+            names.insert(name.clone());
+        }
+        if let Err(e) = fs::copy(&sample.path, output.join(&name)) {
+            eprintln!("Could not copy {:?}: {:?}", sample.path, e);
+            process::exit(1);
+        }
+        total_len += sample.len;
+    }
+    let num_tuples = samples
+        .iter()
+        .flat_map(|sample| sample.tuples.iter())
+        .collect::<HashSet<_>>()
+        .len();
+    eprintln!(
+        "{} inputs, {} ran normally, {} branches and buckets; kept {} inputs of {} bytes",
+        num_inputs,
+        samples.len(),
+        num_tuples,
+        picked.len(),
+        total_len
+    );
+}
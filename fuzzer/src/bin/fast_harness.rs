@@ -30,6 +30,10 @@ impl FastHarness {
             defs::MSAN_OPTIONS_VAR.to_string(),
             defs::MSAN_OPTIONS_CONTENT.to_string(),
         );
+        envs.insert(
+            defs::UBSAN_OPTIONS_VAR.to_string(),
+            defs::UBSAN_OPTIONS_CONTENT.to_string(),
+        );
         envs.insert(
             angora_common::defs::BRANCHES_SHM_ENV_VAR.to_string(),
             shm_id,
@@ -70,9 +74,10 @@ impl FastHarness {
             false,
             angora_common::config::TIME_LIMIT,
             angora_common::config::MEM_LIMIT,
+            None,
         );
 
-        branches.resize();
+        branches.resize().unwrap();
 
         Self {
             branches,
@@ -4,8 +4,8 @@ use clap::{App, Arg};
 
 extern crate angora;
 extern crate angora_common;
-use angora::fuzz_main;
-use angora_common::config::{FuzzerConfig, CONFIG};
+use angora::{fuzz_main, parse_time_limit};
+use angora_common::config::{self, CrashFrameKey, FuzzerConfig, CONFIG};
 
 fn main() {
     let matches = App::new("angora-fuzzer")
@@ -60,7 +60,26 @@ fn main() {
              .short("T")
              .long("time_limit")
              .value_name("TIME")
-             .help("time limit for programs, default is 1(s), the tracking timeout is 12 * TIME")
+             .help("time limit for programs, in seconds (1), milliseconds (50ms) or \"auto\" to calibrate it from the seeds, default is 1(s), the tracking timeout is 20 * TIME")
+             .takes_value(true)
+             .validator(|t| parse_time_limit(&t).map(|_| ())))
+          .arg(Arg::with_name("net")
+             .short("N")
+             .long("net")
+             .value_name("ADDR")
+             .help("Send inputs to the port the program listens on, as tcp://HOST:PORT or udp://HOST:PORT. \"@@PORT\" in the arguments is substituted with a port for each job, PORT plus its index")
+             .takes_value(true))
+          .arg(Arg::with_name("libfuzzer")
+             .short("L")
+             .long("libfuzzer")
+             .help("The program is a libFuzzer harness compiled as a shared object, run it with libfuzzer_loader"))
+          .arg(Arg::with_name("cgroup")
+             .long("cgroup")
+             .help("Limit memory and processes with cgroup v2 instead of RLIMIT_AS, which also works for ASAN. Falls back to RLIMIT_AS if cgroups are not writable"))
+          .arg(Arg::with_name("source_map")
+             .long("source_map")
+             .value_name("FILE")
+             .help("Source map that ANGORA_SOURCE_MAP made the pass write for the fast binary, to annotate cmpids with source locations and export coverage.lcov")
              .takes_value(true))
           .arg(Arg::with_name("bind")
           .short("b")
@@ -114,6 +133,28 @@ fn main() {
           .arg(Arg::with_name("order")
              .long("order")
              .help("using order"))
+          .arg(Arg::with_name("crash_frames")
+             .long("crash_frames")
+             .value_name("FRAMES")
+             .takes_value(true)
+             .help("Number of top stack frames that identify a crash bucket, 0 for all (Default: 3)"))
+          .arg(Arg::with_name("keep_runtime_frames")
+             .long("keep_runtime_frames")
+             .help("Keep the frames inside sanitizer runtimes and libc in crash buckets(Default: false)"))
+          .arg(Arg::with_name("crash_frame_key")
+             .long("crash_frame_key")
+             .value_name("KEY")
+             .help("Identify stack frames in crash buckets by module+offset, or by function name, which symbolizes sanitizer reports(Default: offset)")
+             .possible_values(&["offset", "function"]))
+          .arg(Arg::with_name("queue_metadata")
+             .long("queue_metadata")
+             .help("Write the exec time, edges and tracked conditions of each input in the queue to queue/.meta/(Default: false)"))
+          .arg(Arg::with_name("minimize_crashes")
+             .long("minimize_crashes")
+             .help("Minimize the first crash of each new bucket as id:NNNNNN.min, keeping the bucket(Default: false)"))
+          .arg(Arg::with_name("detect_leaks")
+             .long("detect_leaks")
+             .help("Report memory leaks in ASan builds as crashes, with LSan(Default: false)"))
           /*
           .arg(Arg::with_name("disable_multi_pt")
              .long("disable_multi_pt")
@@ -137,7 +178,18 @@ fn main() {
         .set_enable_multi_pt(matches.occurrences_of("disable_multi_pt") == 0)
         .set_max_priority(value_t!(matches, "max_priority", u16).unwrap_or(std::u16::MAX))
         .set_belong(matches.occurrences_of("belong") != 0)
-        .set_order(matches.occurrences_of("order") != 0);
+        .set_order(matches.occurrences_of("order") != 0)
+        .set_crash_frames(
+            value_t!(matches, "crash_frames", usize).unwrap_or(config::CRASH_BUCKET_FRAMES),
+        )
+        .set_skip_runtime_frames(matches.occurrences_of("keep_runtime_frames") == 0)
+        .set_crash_frame_key(match matches.value_of("crash_frame_key") {
+            Some("function") => CrashFrameKey::Function,
+            _ => CrashFrameKey::ModuleOffset,
+        })
+        .set_queue_metadata(matches.occurrences_of("queue_metadata") != 0)
+        .set_minimize_crashes(matches.occurrences_of("minimize_crashes") != 0)
+        .set_detect_leaks(matches.occurrences_of("detect_leaks") != 0);
     CONFIG.set(config).unwrap();
 
     fuzz_main(
@@ -150,8 +202,12 @@ fn main() {
         value_t!(matches, "bind", usize).ok(),
         value_t!(matches, "thread_jobs", usize).unwrap_or(1),
         value_t!(matches, "memory_limit", u64).unwrap_or(angora_common::config::MEM_LIMIT),
-        value_t!(matches, "time_limit", u64).unwrap_or(angora_common::config::TIME_LIMIT),
+        matches.value_of("time_limit").unwrap_or("1"),
         matches.value_of("search_method").unwrap_or("gd"),
         matches.occurrences_of("sync_afl") > 0,
+        matches.value_of("net"),
+        matches.occurrences_of("libfuzzer") > 0,
+        matches.occurrences_of("cgroup") > 0,
+        matches.value_of("source_map"),
     );
 }
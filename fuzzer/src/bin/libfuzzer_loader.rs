@@ -0,0 +1,167 @@
+//! Runs a libFuzzer harness compiled by Angora (USE_FAST) as a shared object:
+//!     libfuzzer_loader HARNESS.so [INPUT]
+//! The harness starts the fork server, and each child calls `LLVMFuzzerTestOneInput`
+//! in a persistent loop, with the test case from shared memory, INPUT or stdin.
+//! Under `LibFuzzerExecutor`, the loader is the worker instead, and calls it on each
+//! test case the fuzzer asks for in this process.
+use angora_common::defs;
+use byteorder::{LittleEndian, WriteBytesExt};
+use libc::{self, c_char, c_int, c_uint, c_void, ssize_t};
+use std::{
+    env,
+    ffi::{CStr, CString},
+    fs,
+    io::{self, prelude::*},
+    mem,
+    os::unix::net::UnixStream,
+    process, ptr, slice,
+};
+
+type TestOneInputFn = unsafe extern "C" fn(*const u8, usize) -> c_int;
+type InitializeFn = unsafe extern "C" fn(*mut c_int, *mut *mut *mut c_char) -> c_int;
+type TraceInitFn = unsafe extern "C" fn();
+type PersistentLoopFn = unsafe extern "C" fn(c_uint) -> c_int;
+type TestcaseBufFn = unsafe extern "C" fn() -> *const u8;
+type TestcaseLenFn = unsafe extern "C" fn() -> ssize_t;
+type WorkerNextFn = unsafe extern "C" fn();
+
+// Runs in one child before the fork server forks a fresh one.
+const LOOP_CNT: c_uint = 1000;
+
+unsafe fn dlsym<T: Copy>(handle: *mut c_void, name: &str) -> Option<T> {
+    let name = CString::new(name).unwrap();
+    let sym = libc::dlsym(handle, name.as_ptr());
+    if sym.is_null() {
+        None
+    } else {
+        Some(mem::transmute_copy(&sym))
+    }
+}
+
+unsafe fn dlerror() -> String {
+    let err = libc::dlerror();
+    if err.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(err).to_string_lossy().into_owned()
+    }
+}
+
+fn read_input(input: Option<&String>, buf: &mut Vec<u8>) {
+    buf.clear();
+    let res = match input {
+        Some(path) => fs::File::open(path).and_then(|mut f| f.read_to_end(buf)),
+        None => io::stdin().read_to_end(buf),
+    };
+    if let Err(e) = res {
+        eprintln!("Could not read the input: {:?}", e);
+        process::exit(1);
+    }
+}
+
+fn read_testcase<'a>(
+    testcase_buf: TestcaseBufFn,
+    testcase_len: TestcaseLenFn,
+    input: Option<&String>,
+    buf: &'a mut Vec<u8>,
+) -> &'a [u8] {
+    unsafe {
+        let len = testcase_len();
+        if len >= 0 {
+            slice::from_raw_parts(testcase_buf(), len as usize)
+        } else {
+            read_input(input, buf);
+            &buf[..]
+        }
+    }
+}
+
+// For each request of the fuzzer, send the pid, run the test case, and
+// send 0, the wait status of a normal exit. A crash ends the process instead.
+fn run_worker<F: FnMut()>(socket_path: &str, mut run: F) {
+    let mut socket = match UnixStream::connect(socket_path) {
+        Ok(sock) => sock,
+        Err(e) => {
+            eprintln!("Couldn't connect: {:?}", e);
+            process::exit(1);
+        },
+    };
+    let mut sig_buf = [0; 4];
+    while socket.read_exact(&mut sig_buf).is_ok() {
+        if socket
+            .write_i32::<LittleEndian>(process::id() as i32)
+            .is_err()
+        {
+            break;
+        }
+        run();
+        if socket.write_i32::<LittleEndian>(0).is_err() {
+            break;
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        eprintln!("Usage: {} HARNESS.so [INPUT]", args[0]);
+        process::exit(1);
+    }
+
+    unsafe {
+        let so = CString::new(args[1].as_str()).unwrap();
+        let handle = libc::dlopen(so.as_ptr(), libc::RTLD_NOW | libc::RTLD_GLOBAL);
+        if handle.is_null() {
+            eprintln!("Could not load {}: {}", args[1], dlerror());
+            process::exit(1);
+        }
+
+        let test_one_input: TestOneInputFn = dlsym(handle, "LLVMFuzzerTestOneInput")
+            .expect("The harness does not define LLVMFuzzerTestOneInput.");
+        let trace_init: TraceInitFn =
+            dlsym(handle, "__trace_init").expect("The harness is not compiled by Angora.");
+        let persistent_loop: PersistentLoopFn = dlsym(handle, "__angora_persistent_loop")
+            .expect("The harness is not compiled by Angora.");
+        let testcase_buf: TestcaseBufFn = dlsym(handle, "__angora_fuzz_testcase_buf")
+            .expect("The harness is not compiled by Angora.");
+        let testcase_len: TestcaseLenFn = dlsym(handle, "__angora_fuzz_testcase_len")
+            .expect("The harness is not compiled by Angora.");
+
+        if let Some(initialize) = dlsym::<InitializeFn>(handle, "LLVMFuzzerInitialize") {
+            // The harness sees the loader as the program, as libFuzzer passes its own argv.
+            let c_args: Vec<CString> = args[..1]
+                .iter()
+                .chain(&args[2..])
+                .map(|a| CString::new(a.as_str()).unwrap())
+                .collect();
+            let mut c_argv: Vec<*mut c_char> =
+                c_args.iter().map(|a| a.as_ptr() as *mut c_char).collect();
+            c_argv.push(ptr::null_mut());
+            let mut argc = c_args.len() as c_int;
+            let mut argv = c_argv.as_mut_ptr();
+            initialize(&mut argc, &mut argv);
+        }
+
+        let input = args.get(2);
+        let mut buf = vec![];
+
+        if let Ok(socket_path) = env::var(defs::WORKER_SOCKET_PATH_VAR) {
+            let worker_next: WorkerNextFn = dlsym(handle, "__angora_worker_next")
+                .expect("The harness is not compiled by Angora.");
+            run_worker(&socket_path, || {
+                worker_next();
+                let data = read_testcase(testcase_buf, testcase_len, input, &mut buf);
+                test_one_input(data.as_ptr(), data.len());
+            });
+            return;
+        }
+
+        // Only the children of the fork server return.
+        trace_init();
+
+        while persistent_loop(LOOP_CNT) != 0 {
+            let data = read_testcase(testcase_buf, testcase_len, input, &mut buf);
+            test_one_input(data.as_ptr(), data.len());
+        }
+    }
+}
@@ -0,0 +1,260 @@
+//! Prints the branches that inputs cover in the fast binary:
+//!     showmap -i INPUT [-o OUTPUT] [--json] [--union] -- /path/to/fast.bin ARGS @@
+//! Each line is "INDEX:BUCKET", where INDEX is the entry of the branch table and
+//! BUCKET the bit of its hit count bucket (1, 2, 3, 4-7, 8-15, 16-31, 32-127, 128+).
+//! If INPUT is a directory, every file in it is run, and OUTPUT is a directory
+//! with a map for each of them, or a single map of all of them with --union.
+use angora::{
+    branches::{Branches, GlobalBranches},
+    cond_stmt::ShmConds,
+    executor::{pipe_fd::PipeFd, Forksrv, StatusType},
+    parse_time_limit,
+};
+use angora_common::{config, defs};
+use clap::*;
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fs,
+    io::{self, prelude::*},
+    path::{Path, PathBuf},
+    process::{self, Command},
+    sync::Arc,
+};
+
+type BranchMap = BTreeMap<usize, u8>;
+
+struct ShowMap {
+    branches: Branches,
+    // Keeps the shared memory of the conditions alive.
+    _t_conds: ShmConds,
+    fd: PipeFd,
+    is_stdin: bool,
+    forksrv: Forksrv,
+}
+
+impl ShowMap {
+    fn new(pargs: Vec<String>, tmp_dir: &Path, time_limit: u64, mem_limit: u64) -> Self {
+        let shm_id = format!("angora_showmap_{}-shm", process::id());
+        let global_branches = Arc::new(GlobalBranches::new());
+        let mut branches = Branches::new(global_branches, &shm_id);
+        let t_conds = ShmConds::new();
+
+        let mut envs = HashMap::new();
+        envs.insert(
+            defs::ASAN_OPTIONS_VAR.to_string(),
+            defs::ASAN_OPTIONS_CONTENT.to_string(),
+        );
+        envs.insert(
+            defs::MSAN_OPTIONS_VAR.to_string(),
+            defs::MSAN_OPTIONS_CONTENT.to_string(),
+        );
+        envs.insert(
+            defs::UBSAN_OPTIONS_VAR.to_string(),
+            defs::UBSAN_OPTIONS_CONTENT.to_string(),
+        );
+        envs.insert(defs::BRANCHES_SHM_ENV_VAR.to_string(), shm_id);
+        envs.insert(
+            defs::COND_STMT_ENV_VAR.to_string(),
+            t_conds.get_id().to_string(),
+        );
+        let clang_lib = Command::new("llvm-config")
+            .arg("--libdir")
+            .output()
+            .expect("Can't find llvm-config")
+            .stdout;
+        let clang_lib = String::from_utf8(clang_lib).unwrap();
+        let ld_library = "$LD_LIBRARY_PATH:".to_string() + clang_lib.trim();
+        envs.insert(defs::LD_LIBRARY_PATH_VAR.to_string(), ld_library);
+
+        let input_file = tmp_dir.join("cur_input").to_str().unwrap().to_owned();
+        let socket_path = tmp_dir.join("socket").to_str().unwrap().to_owned();
+        let fd = PipeFd::new(&input_file);
+        let mut is_stdin = true;
+        let args = pargs[1..]
+            .iter()
+            .map(|arg| {
+                if arg == "@@" {
+                    is_stdin = false;
+                    input_file.clone()
+                } else {
+                    arg.clone()
+                }
+            })
+            .collect();
+        let forksrv = Forksrv::new(
+            &socket_path,
+            &(pargs[0].clone(), args),
+            &envs,
+            fd.as_raw_fd(),
+            is_stdin,
+            false,
+            time_limit,
+            mem_limit,
+            None,
+        );
+
+        branches.resize().unwrap();
+
+        Self {
+            branches,
+            _t_conds: t_conds,
+            fd,
+            is_stdin,
+            forksrv,
+        }
+    }
+
+    fn run(&mut self, buf: &Vec<u8>) -> (StatusType, BranchMap) {
+        self.fd.write_buf(buf);
+        if self.is_stdin {
+            self.fd.rewind();
+        }
+        self.branches.clear_trace();
+        let status = self.forksrv.run().status;
+        (status, self.branches.get_path().into_iter().collect())
+    }
+}
+
+fn format_map(map: &BranchMap, json: bool) -> String {
+    if json {
+        serde_json::to_string_pretty(map).expect("Could not serialize!") + "\n"
+    } else {
+        map.iter()
+            .map(|(i, bucket)| format!("{}:{}\n", i, bucket))
+            .collect()
+    }
+}
+
+fn write_map(map: &BranchMap, json: bool, output: Option<&Path>) {
+    let res = match output {
+        Some(path) => fs::write(path, format_map(map, json)),
+        None => io::stdout().write_all(format_map(map, json).as_bytes()),
+    };
+    if let Err(e) = res {
+        eprintln!("Could not write the map: {:?}", e);
+        process::exit(1);
+    }
+}
+
+fn list_inputs(input: &Path) -> Vec<PathBuf> {
+    if !input.is_dir() {
+        return vec![input.to_path_buf()];
+    }
+    let mut inputs: Vec<PathBuf> = fs::read_dir(input)
+        .expect("Could not read the input directory")
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    inputs.sort();
+    inputs
+}
+
+fn main() {
+    let matches = App::new("showmap")
+        .about("Print the branches that inputs cover in the fast binary.")
+        .arg(Arg::with_name("input")
+            .short("i")
+            .long("input")
+            .value_name("INPUT")
+            .help("An input file, or a directory of them")
+            .takes_value(true)
+            .required(true))
+        .arg(Arg::with_name("output")
+            .short("o")
+            .long("output")
+            .value_name("OUTPUT")
+            .help("File of the map, or a directory of maps if the input is a directory without --union. Standard output by default")
+            .takes_value(true))
+        .arg(Arg::with_name("json")
+            .long("json")
+            .help("Print a JSON object of index to bucket instead of INDEX:BUCKET lines"))
+        .arg(Arg::with_name("union")
+            .long("union")
+            .help("Print a single map of all the inputs, with the union of their buckets"))
+        .arg(Arg::with_name("time_limit")
+            .short("T")
+            .long("time_limit")
+            .value_name("TIME")
+            .help("Time limit in seconds (1) or milliseconds (50ms)")
+            .takes_value(true)
+            .validator(|t| {
+                parse_time_limit(&t)?
+                    .map(|_| ())
+                    .ok_or_else(|| "showmap cannot calibrate the time limit".to_owned())
+            }))
+        .arg(Arg::with_name("memory_limit")
+            .short("M")
+            .long("memory_limit")
+            .value_name("MEM")
+            .help("Memory limit in MB")
+            .takes_value(true))
+        .arg(Arg::with_name("pargs")
+            .help("Targeted program (USE_FAST) and arguments. Any \"@@\" will be substituted with the input filename.")
+            .required(true)
+            .multiple(true)
+            .allow_hyphen_values(true)
+            .last(true)
+            .index(1))
+        .get_matches();
+
+    let input = Path::new(matches.value_of("input").unwrap());
+    let output = matches.value_of("output").map(Path::new);
+    let json = matches.is_present("json");
+    let union = matches.is_present("union");
+    let time_limit = matches
+        .value_of("time_limit")
+        .and_then(|t| parse_time_limit(t).unwrap())
+        .unwrap_or(config::TIME_LIMIT);
+    let mem_limit = value_t!(matches, "memory_limit", u64).unwrap_or(config::MEM_LIMIT);
+    let inputs = list_inputs(input);
+    let per_input = input.is_dir() && !union;
+    if let (true, Some(dir)) = (per_input, output) {
+        fs::create_dir_all(dir).expect("Could not create the output directory");
+    }
+
+    let tmp_dir = env::temp_dir().join(format!("angora_showmap_{}", process::id()));
+    fs::create_dir_all(&tmp_dir).expect("Could not create the temporary directory");
+    let mut showmap = ShowMap::new(
+        matches.values_of_lossy("pargs").unwrap(),
+        &tmp_dir,
+        time_limit,
+        mem_limit,
+    );
+
+    let mut union_map = BranchMap::new();
+    for path in &inputs {
+        let buf = match fs::read(path) {
+            Ok(buf) => buf,
+            Err(e) => {
+                eprintln!("Could not read {:?}: {:?}", path, e);
+                continue;
+            },
+        };
+        let (status, map) = showmap.run(&buf);
+        if status != StatusType::Normal {
+            eprintln!("{}: {:?}", path.display(), status);
+        }
+        if union {
+            for (i, bucket) in map {
+                *union_map.entry(i).or_insert(0) |= bucket;
+            }
+        } else if per_input {
+            match output {
+                Some(dir) => write_map(&map, json, Some(&dir.join(path.file_name().unwrap()))),
+                None => {
+                    println!("# {}", path.display());
+                    write_map(&map, json, None);
+                },
+            }
+        } else {
+            write_map(&map, json, output);
+        }
+    }
+    if union {
+        write_map(&union_map, json, output);
+        eprintln!("{} inputs, {} branches", inputs.len(), union_map.len());
+    }
+
+    drop(showmap);
+    let _ = fs::remove_dir_all(&tmp_dir);
+}
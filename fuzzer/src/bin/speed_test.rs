@@ -1,12 +1,79 @@
 extern crate angora;
+extern crate angora_common;
 
-use angora::executor::Forksrv;
-use std::{collections::HashMap, env, os::unix::io::RawFd, time::SystemTime};
+use angora::{branches, executor::Forksrv};
+use angora_common::config::BRANCHES_SIZE;
+use std::{
+    collections::HashMap,
+    env,
+    os::unix::io::RawFd,
+    time::{Duration, Instant, SystemTime},
+};
 
 static FUZZER_ID_VAR: &str = "ANGORA_FUZZER_ID";
-const TIME_LIMIT: u64 = 5;
+const TIME_LIMIT: u64 = 5000; // ms
 const MEM_LIMIT: u64 = 2000;
 
+// Branches hit by one execution in the `has_new` benchmark.
+const BENCH_HITS: usize = 2000;
+const BENCH_ROUNDS: u32 = 2000;
+
+// `has_new` before the zero chunks were skipped: collect the whole path, then probe it.
+fn has_new_by_path(table: &[u16], virgin: &[u8]) -> (bool, usize) {
+    let mut path = Vec::<(usize, u8)>::new();
+    for (i, &v) in table.iter().enumerate() {
+        let c = if v > 255 { 255 } else { v as u8 };
+        if c > 0 {
+            // Close enough to the bucket for timing.
+            path.push((i, c.next_power_of_two()));
+        }
+    }
+    let mut to_write = vec![];
+    for &br in &path {
+        let gb_v = virgin[br.0];
+        if (br.1 & gb_v) > 0 {
+            to_write.push((br.0, gb_v & (!br.1)));
+        }
+    }
+    (!to_write.is_empty(), path.len())
+}
+
+// Time of checking an execution that covers nothing new, the common case.
+fn bench_has_new(num_hits: usize) {
+    let mut table = vec![0_u16; BRANCHES_SIZE];
+    let mut virgin = vec![255_u8; BRANCHES_SIZE];
+    let mut seed = 0x2545f491_usize;
+    for _ in 0..num_hits {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        let i = (seed >> 16) % BRANCHES_SIZE;
+        table[i] = 1;
+        virgin[i] = 0;
+    }
+
+    let per_exec = |t: Duration| t / BENCH_ROUNDS;
+    let t = Instant::now();
+    for _ in 0..BENCH_ROUNDS {
+        let (is_new, edge_num) = has_new_by_path(&table, &virgin);
+        assert!(!is_new && edge_num > 0);
+    }
+    println!("path then probe: {:?} per exec", per_exec(t.elapsed()));
+
+    let virgin_map = branches::VirginMap::new();
+    virgin_map.resize(BRANCHES_SIZE).unwrap();
+    for (i, &v) in virgin.iter().enumerate() {
+        virgin_map.clear(i, !v);
+    }
+    let mut new = vec![];
+    let t = Instant::now();
+    for _ in 0..BENCH_ROUNDS {
+        let edge_num = branches::find_new_branches(&table, &virgin_map, &mut new);
+        assert!(new.is_empty() && edge_num > 0);
+    }
+    println!("find_new_branches: {:?} per exec", per_exec(t.elapsed()));
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
     if args.len() < 2 {
@@ -14,6 +81,15 @@ fn main() {
         return;
     }
 
+    if args[1] == "--has_new" {
+        let num_hits = args
+            .get(2)
+            .and_then(|n| n.parse().ok())
+            .unwrap_or(BENCH_HITS);
+        bench_has_new(num_hits);
+        return;
+    }
+
     let prom_bin = args[1].clone();
     let prom_args = vec![args[2].clone()];
 
@@ -33,6 +109,7 @@ fn main() {
         false,
         TIME_LIMIT,
         MEM_LIMIT,
+        None,
     );
 
     let init_t = SystemTime::now();
@@ -0,0 +1,363 @@
+//! Shrinks an input while it keeps the same behavior, like afl-tmin:
+//!     tmin -i INPUT [-o OUTPUT] [--objective crash|hang|coverage] [-s SAN_BIN] -- /path/to/fast.bin ARGS @@
+//! crash: the same crash bucket, from the report of the sanitized program if it is
+//! given, or else from the signal and the path in the fast program.
+//! hang: still times out in the fast program.
+//! coverage: the same branches and hit count buckets in the fast program.
+//! Blocks are removed, and blocks, byte values and bytes replaced with '0'.
+use angora::{
+    branches::{Branches, CrashInfo, GlobalBranches},
+    cond_stmt::ShmConds,
+    executor::{pipe_fd::PipeFd, ExecStatus, Forksrv, StatusType},
+    minimize, parse_time_limit,
+};
+use angora_common::{
+    config::{self, CrashFrameKey, FuzzerConfig, CONFIG},
+    defs,
+};
+use clap::*;
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::prelude::*,
+    path::Path,
+    process::{self, Command, Stdio},
+    sync::Arc,
+    time::Duration,
+};
+use wait_timeout::ChildExt;
+
+const MAX_EXECS: usize = 10000;
+
+#[derive(Clone, Copy)]
+enum Objective {
+    Crash,
+    Hang,
+    Coverage,
+}
+
+#[derive(PartialEq)]
+enum Outcome {
+    // The crash bucket.
+    Crash(String),
+    Hang,
+    Path(Vec<(usize, u8)>),
+}
+
+struct Tmin {
+    branches: Branches,
+    // Keeps the shared memory of the conditions alive.
+    _t_conds: ShmConds,
+    fd: PipeFd,
+    input_file: String,
+    is_stdin: bool,
+    envs: HashMap<String, String>,
+    forksrv: Forksrv,
+    san: Option<(String, Vec<String>)>,
+    san_time_limit: u64,
+}
+
+impl Tmin {
+    fn new(
+        pargs: Vec<String>,
+        san_target: Option<&str>,
+        tmp_dir: &Path,
+        time_limit: u64,
+        mem_limit: u64,
+    ) -> Self {
+        let shm_id = format!("angora_tmin_{}-shm", process::id());
+        let global_branches = Arc::new(GlobalBranches::new());
+        let mut branches = Branches::new(global_branches, &shm_id);
+        let t_conds = ShmConds::new();
+
+        let mut envs = HashMap::new();
+        // Symbolized if crash buckets need the function names, as in the fuzzer.
+        let symbolize = |options: &str| {
+            if FuzzerConfig::get().crash_frame_key() == CrashFrameKey::Function {
+                options.replace("symbolize=0", "symbolize=1")
+            } else {
+                options.to_owned()
+            }
+        };
+        envs.insert(
+            defs::ASAN_OPTIONS_VAR.to_string(),
+            symbolize(defs::ASAN_OPTIONS_CONTENT),
+        );
+        envs.insert(
+            defs::MSAN_OPTIONS_VAR.to_string(),
+            symbolize(defs::MSAN_OPTIONS_CONTENT),
+        );
+        envs.insert(
+            defs::UBSAN_OPTIONS_VAR.to_string(),
+            symbolize(defs::UBSAN_OPTIONS_CONTENT),
+        );
+        envs.insert(defs::BRANCHES_SHM_ENV_VAR.to_string(), shm_id);
+        envs.insert(
+            defs::COND_STMT_ENV_VAR.to_string(),
+            t_conds.get_id().to_string(),
+        );
+        let clang_lib = Command::new("llvm-config")
+            .arg("--libdir")
+            .output()
+            .expect("Can't find llvm-config")
+            .stdout;
+        let clang_lib = String::from_utf8(clang_lib).unwrap();
+        let ld_library = "$LD_LIBRARY_PATH:".to_string() + clang_lib.trim();
+        envs.insert(defs::LD_LIBRARY_PATH_VAR.to_string(), ld_library);
+
+        let input_file = tmp_dir.join("cur_input").to_str().unwrap().to_owned();
+        let socket_path = tmp_dir.join("socket").to_str().unwrap().to_owned();
+        let fd = PipeFd::new(&input_file);
+        let is_stdin = !pargs.iter().any(|arg| arg == "@@");
+        let args: Vec<String> = pargs[1..]
+            .iter()
+            .map(|arg| {
+                if arg == "@@" {
+                    input_file.clone()
+                } else {
+                    arg.clone()
+                }
+            })
+            .collect();
+        let forksrv = Forksrv::new(
+            &socket_path,
+            &(pargs[0].clone(), args.clone()),
+            &envs,
+            fd.as_raw_fd(),
+            is_stdin,
+            false,
+            time_limit,
+            mem_limit,
+            None,
+        );
+
+        branches.resize().unwrap();
+
+        Self {
+            branches,
+            _t_conds: t_conds,
+            fd,
+            input_file,
+            is_stdin,
+            envs,
+            forksrv,
+            san: san_target.map(|san| (san.to_owned(), args)),
+            san_time_limit: (time_limit * config::TIME_LIMIT_SAN_RATIO)
+                .max(config::TIME_LIMIT_SAN_MIN),
+        }
+    }
+
+    /// What `buf` does, if it is what the objective keeps.
+    fn outcome(&mut self, buf: &Vec<u8>, objective: Objective) -> Option<Outcome> {
+        self.fd.write_buf(buf);
+        if self.is_stdin {
+            self.fd.rewind();
+        }
+        self.branches.clear_trace();
+        let status = self.forksrv.run();
+        match (objective, status.status) {
+            (Objective::Hang, StatusType::Timeout) => Some(Outcome::Hang),
+            (Objective::Coverage, StatusType::Normal) => {
+                Some(Outcome::Path(self.branches.get_path()))
+            },
+            (Objective::Crash, StatusType::Crash) => {
+                let path_hash = self.branches.path_hash();
+                let crash_info = match self.run_with_san() {
+                    None => CrashInfo::from_status(&status, path_hash),
+                    Some((san_status, _)) if san_status.status != StatusType::Crash => return None,
+                    Some((_, san_stderr)) => CrashInfo::from_output_string(&san_stderr)
+                        .unwrap_or_else(|| CrashInfo::from_status(&status, path_hash)),
+                };
+                Some(Outcome::Crash(crash_info.bucket_hash()))
+            },
+            _ => None,
+        }
+    }
+
+    // Runs the input file that the fast program has just run.
+    fn run_with_san(&self) -> Option<(ExecStatus, String)> {
+        let san = self.san.as_ref()?;
+        let stdin = if self.is_stdin {
+            Stdio::from(fs::File::open(&self.input_file).expect("Could not open the input"))
+        } else {
+            Stdio::null()
+        };
+        let mut child = Command::new(&san.0)
+            .args(&san.1)
+            .stdin(stdin)
+            .envs(&self.envs)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("Could not run the sanitized program");
+        let mut status = match child
+            .wait_timeout(Duration::from_millis(self.san_time_limit))
+            .unwrap()
+        {
+            Some(exit_status) => ExecStatus::from_exit_status(&exit_status, true),
+            None => {
+                child.kill().expect("Could not send kill signal to child.");
+                child.wait().expect("Error during waiting for child.");
+                ExecStatus::new(StatusType::Timeout)
+            },
+        };
+        let mut stderr = vec![];
+        if let Some(mut f) = child.stderr {
+            let _ = f.read_to_end(&mut stderr);
+        }
+        let stderr = String::from_utf8_lossy(&stderr).into_owned();
+        status.update_from_san_report(&stderr);
+        Some((status, stderr))
+    }
+}
+
+fn main() {
+    let matches = App::new("tmin")
+        .about("Shrink an input while it keeps crashing in the same bucket, hanging, or covering the same branches.")
+        .arg(Arg::with_name("input")
+            .short("i")
+            .long("input")
+            .value_name("INPUT")
+            .help("The input to shrink")
+            .takes_value(true)
+            .required(true))
+        .arg(Arg::with_name("output")
+            .short("o")
+            .long("output")
+            .value_name("OUTPUT")
+            .help("File of the minimized input, INPUT.min by default")
+            .takes_value(true))
+        .arg(Arg::with_name("objective")
+            .long("objective")
+            .value_name("OBJECTIVE")
+            .help("What the input has to keep doing(Default: crash)")
+            .possible_values(&["crash", "hang", "coverage"]))
+        .arg(Arg::with_name("sanitizer_target")
+            .short("s")
+            .long("sanitizer_target")
+            .value_name("PROM")
+            .help("Sanitized program, which gives the crash buckets its report. Same arguments as the fast one")
+            .takes_value(true))
+        .arg(Arg::with_name("max_execs")
+            .short("e")
+            .long("max_execs")
+            .value_name("EXECS")
+            .help("Stop after this many executions(Default: 10000)")
+            .takes_value(true))
+        .arg(Arg::with_name("time_limit")
+            .short("T")
+            .long("time_limit")
+            .value_name("TIME")
+            .help("Time limit in seconds (1) or milliseconds (50ms), also what counts as a hang")
+            .takes_value(true)
+            .validator(|t| {
+                parse_time_limit(&t)?
+                    .map(|_| ())
+                    .ok_or_else(|| "tmin cannot calibrate the time limit".to_owned())
+            }))
+        .arg(Arg::with_name("memory_limit")
+            .short("M")
+            .long("memory_limit")
+            .value_name("MEM")
+            .help("Memory limit in MB")
+            .takes_value(true))
+        .arg(Arg::with_name("crash_frames")
+            .long("crash_frames")
+            .value_name("NUM")
+            .help("Top stack frames in a crash bucket, 0 for all, as for the fuzzer")
+            .takes_value(true))
+        .arg(Arg::with_name("crash_frame_key")
+            .long("crash_frame_key")
+            .value_name("KEY")
+            .help("Identify stack frames in crash buckets by module+offset, or by function name, as for the fuzzer")
+            .possible_values(&["offset", "function"]))
+        .arg(Arg::with_name("pargs")
+            .help("Targeted program (USE_FAST) and arguments. Any \"@@\" will be substituted with the input filename.")
+            .required(true)
+            .multiple(true)
+            .allow_hyphen_values(true)
+            .last(true)
+            .index(1))
+        .get_matches();
+
+    let mut fuzzer_config = FuzzerConfig::new();
+    fuzzer_config
+        .set_crash_frames(
+            value_t!(matches, "crash_frames", usize).unwrap_or(config::CRASH_BUCKET_FRAMES),
+        )
+        .set_crash_frame_key(match matches.value_of("crash_frame_key") {
+            Some("function") => CrashFrameKey::Function,
+            _ => CrashFrameKey::ModuleOffset,
+        });
+    CONFIG.set(fuzzer_config).unwrap();
+
+    let input = matches.value_of("input").unwrap();
+    let output = matches
+        .value_of("output")
+        .map(|o| o.to_owned())
+        .unwrap_or_else(|| format!("{}.min", input));
+    let objective = match matches.value_of("objective") {
+        Some("hang") => Objective::Hang,
+        Some("coverage") => Objective::Coverage,
+        _ => Objective::Crash,
+    };
+    let max_execs = value_t!(matches, "max_execs", usize).unwrap_or(MAX_EXECS);
+    let time_limit = matches
+        .value_of("time_limit")
+        .and_then(|t| parse_time_limit(t).unwrap())
+        .unwrap_or(match objective {
+            Objective::Hang => config::TIME_LIMIT_HANG_MIN,
+            _ => config::TIME_LIMIT,
+        });
+    let mem_limit = value_t!(matches, "memory_limit", u64).unwrap_or(config::MEM_LIMIT);
+    let buf = match fs::read(input) {
+        Ok(buf) => buf,
+        Err(e) => {
+            eprintln!("Could not read {:?}: {:?}", input, e);
+            process::exit(1);
+        },
+    };
+
+    let tmp_dir = env::temp_dir().join(format!("angora_tmin_{}", process::id()));
+    fs::create_dir_all(&tmp_dir).expect("Could not create the temporary directory");
+    let mut tmin = Tmin::new(
+        matches.values_of_lossy("pargs").unwrap(),
+        matches.value_of("sanitizer_target"),
+        &tmp_dir,
+        time_limit,
+        mem_limit,
+    );
+
+    let min_buf = match tmin.outcome(&buf, objective) {
+        Some(orig) => {
+            let mut execs = 0;
+            let min_buf = minimize::tmin(&buf, max_execs, |candidate| {
+                execs += 1;
+                tmin.outcome(candidate, objective).as_ref() == Some(&orig)
+            });
+            eprintln!(
+                "{} to {} bytes in {} executions",
+                buf.len(),
+                min_buf.len(),
+                execs
+            );
+            Some(min_buf)
+        },
+        None => {
+            eprintln!("The input does not do what the objective keeps, e.g., it does not crash.");
+            None
+        },
+    };
+
+    drop(tmin);
+    let _ = fs::remove_dir_all(&tmp_dir);
+    match min_buf {
+        Some(min_buf) => {
+            if let Err(e) = fs::write(&output, min_buf) {
+                eprintln!("Could not write {:?}: {:?}", output, e);
+                process::exit(1);
+            }
+        },
+        None => process::exit(1),
+    }
+}
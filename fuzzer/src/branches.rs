@@ -1,16 +1,25 @@
-use crate::executor::StatusType;
-use angora_common::{config::BRANCHES_SIZE, shm::*};
+use crate::{
+    crash_report,
+    executor::{ExecStatus, StatusType},
+};
+use angora_common::{
+    config::{CrashFrameKey, FuzzerConfig, BRANCHES_SIZE},
+    defs,
+    shm::*,
+};
+use once_cell::sync::OnceCell;
+use serde_derive::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 #[cfg(feature = "unstable")]
 use std::intrinsics::unlikely;
 use std::{
     self,
     collections::HashSet,
-    ops::{Deref, DerefMut},
+    fs,
     path::Path,
     sync::{
-        atomic::{AtomicUsize, Ordering},
-        Arc, RwLock,
+        atomic::{AtomicU8, AtomicUsize, Ordering},
+        Arc, Mutex,
     },
 };
 
@@ -34,70 +43,180 @@ static COUNT_LOOKUP: [u8; 256] = [
 type TableEntryType = u16;
 const ENTRY_SIZE: usize = std::mem::size_of::<TableEntryType>();
 
-macro_rules! cast {
-    ($ptr:expr) => {{
-        unsafe { std::mem::transmute($ptr) }
-    }};
+// Entries checked at once for zeros, a cache line.
+const SKIP_CHUNK: usize = 64 / ENTRY_SIZE;
+
+/// Call `f(index, bucket)` on each branch hit in `table`. Most of the table is zero,
+/// so it skips a chunk at a time, with SIMD if the compiler can.
+#[inline]
+fn for_each_hit<F: FnMut(usize, u8)>(table: &[TableEntryType], mut f: F) {
+    for (n, chunk) in table.chunks(SKIP_CHUNK).enumerate() {
+        let hits = chunk.iter().fold(0, |acc, &v| acc | v);
+        #[cfg(feature = "unstable")]
+        {
+            if unsafe { !unlikely(hits > 0) } {
+                continue;
+            }
+        }
+        #[cfg(not(feature = "unstable"))]
+        {
+            if hits == 0 {
+                continue;
+            }
+        }
+        let base = n * SKIP_CHUNK;
+        for (j, &v) in chunk.iter().enumerate() {
+            if v > 0 {
+                let c = if v > 255 { 255 } else { v as usize };
+                f(base + j, COUNT_LOOKUP[c]);
+            }
+        }
+    }
 }
 
-#[derive(Debug, Clone, Hash)]
-pub struct Trace {
-    inner: Vec<u8>,
-    top: Vec<u16>,
+/// Call `f(index)` on each branch that is in only one of the paths,
+/// or has different buckets. Both are sorted by index.
+fn for_each_diff<F: FnMut(usize)>(a: &[(usize, u8)], b: &[(usize, u8)], mut f: F) {
+    let (mut a, mut b) = (a.iter().peekable(), b.iter().peekable());
+    loop {
+        match (a.peek(), b.peek()) {
+            (Some(&&(i, x)), Some(&&(j, y))) => {
+                if i == j {
+                    if x != y {
+                        f(i);
+                    }
+                    a.next();
+                    b.next();
+                } else if i < j {
+                    f(i);
+                    a.next();
+                } else {
+                    f(j);
+                    b.next();
+                }
+            },
+            (Some(&&(i, _)), None) => {
+                f(i);
+                a.next();
+            },
+            (None, Some(&&(j, _))) => {
+                f(j);
+                b.next();
+            },
+            (None, None) => break,
+        }
+    }
 }
 
-impl Trace {
-    pub fn empty() -> Self {
-        Self {
-            inner: Vec::new(),
-            top: Vec::new(),
+/// Put the branches hit in `table` whose buckets are still in `virgin` into `new`,
+/// and return the number of branches hit.
+pub fn find_new_branches(
+    table: &[TableEntryType],
+    virgin: &VirginMap,
+    new: &mut Vec<(usize, u8)>,
+) -> usize {
+    new.clear();
+    let mut edge_num = 0;
+    for_each_hit(table, |i, bucket| {
+        edge_num += 1;
+        if (bucket & virgin.get(i)) > 0 {
+            new.push((i, bucket));
         }
+    });
+    edge_num
+}
+
+/// The buckets of each branch that no input has covered yet, 255 if the branch is
+/// not covered at all. All the executors update it without locks.
+#[derive(Debug, Default)]
+pub struct VirginMap {
+    // Sized once the first program has allocated its branch table. All the programs
+    // share its size, so it never grows, which lets the executors update it without locks.
+    inner: OnceCell<Box<[AtomicU8]>>,
+}
+
+impl VirginMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn get_inner(&self) -> &[AtomicU8] {
+        self.inner.get().map_or(&[], |inner| inner)
     }
 
     pub fn len(&self) -> usize {
-        self.inner.len()
+        self.get_inner().len()
     }
 
-    pub fn resize(&mut self, new_len: usize) {
-        self.inner.resize(new_len, 255_u8);
-        self.top.resize(new_len, 255_u16);
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
-}
 
-impl PartialEq for Trace {
-    fn eq(&self, other: &Self) -> bool {
-        assert_eq!(self.len(), other.len());
-        let bucket_stat = self
+    /// Size the map for a branch table of `new_len`, which cannot grow once it is set.
+    pub fn resize(&self, new_len: usize) -> Result<(), String> {
+        if new_len == 0 {
+            return Ok(());
+        }
+        let inner = self
             .inner
-            .iter()
-            .zip(other.inner.iter())
-            .fold(true, |acc, (x, y)| acc & (*x == (*x | *y)));
-        let top_stat = self
-            .top
-            .iter()
-            .zip(other.top.iter())
-            .fold(true, |acc, (x, y)| acc & (*x >= *y));
-        bucket_stat && top_stat
-        // self.inner == other.inner
+            .get_or_init(|| (0..new_len).map(|_| AtomicU8::new(255)).collect());
+        if inner.len() < new_len {
+            return Err(format!(
+                "Branch table grows from {} to {}, but the maps shared by all the executors cannot grow.",
+                inner.len(),
+                new_len
+            ));
+        }
+        Ok(())
     }
-}
 
-impl Eq for Trace {}
+    pub fn get(&self, i: usize) -> u8 {
+        self.get_inner()[i].load(Ordering::Relaxed)
+    }
 
-impl Deref for Trace {
-    type Target = Vec<u8>;
-    fn deref(&self) -> &Self::Target {
-        &self.inner
+    /// Clear `bucket` of branch `i`, and return its buckets before, so that only one
+    /// of the executors that cover the same new bucket at once sees it as new.
+    pub fn clear(&self, i: usize, bucket: u8) -> u8 {
+        self.get_inner()[i].fetch_and(!bucket, Ordering::Relaxed)
+    }
+
+    fn to_vec(&self) -> Vec<u8> {
+        self.get_inner()
+            .iter()
+            .map(|v| v.load(Ordering::Relaxed))
+            .collect()
     }
-}
 
-impl DerefMut for Trace {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.inner
+    fn save(&self, path: &Path) {
+        // Write it at once, so that a fuzzer killed meanwhile leaves the old one.
+        let tmp = path.with_extension("tmp");
+        if let Err(e) = fs::write(&tmp, self.to_vec()).and_then(|_| fs::rename(&tmp, path)) {
+            warn!("Could not save branches to {:?}: {:?}", path, e);
+        }
+    }
+
+    /// Read the map saved in `path`, which should have the same size.
+    fn load(&self, path: &Path) -> Option<Vec<u8>> {
+        match fs::read(path) {
+            Ok(buf) if buf.len() == self.len() => Some(buf),
+            Ok(buf) => {
+                warn!(
+                    "Branches in {:?} have size {}, but the program has {}.",
+                    path,
+                    buf.len(),
+                    self.len()
+                );
+                None
+            },
+            Err(e) => {
+                warn!("Could not load branches from {:?}: {:?}", path, e);
+                None
+            },
+        }
     }
 }
 
-#[derive(Hash, Eq, PartialEq, Debug)]
+#[derive(Hash, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub struct CrashInfo {
     error: String,
     stack_hash: [u8; 32],
@@ -105,72 +224,129 @@ pub struct CrashInfo {
 }
 
 impl CrashInfo {
-    pub fn from_output_string(s: &str) -> Self {
-        let mut san_lines = false;
-        let output_lines = s.lines();
-
-        let mut stacks = Vec::new();
-        let mut summary = String::new();
-        let mut error = String::new();
-        for line in output_lines {
-            if line.contains("==ERROR: AddressSanitize") {
-                san_lines = true;
-                let mut word = line.split_whitespace();
-                error = word.nth(2).unwrap_or_default().to_string();
-            }
-            if line.contains("SUMMARY: AddressSanitizer") {
-                summary = line.to_string();
-            }
-            if san_lines && line.starts_with("    #") {
-                // stacks.push(san_lines.to_string());
-                let words = line.split_whitespace();
-                let loc = words.last().unwrap_or_default();
-                stacks.push(loc.to_string());
-            }
-        }
+    /// From the bug type and stack in a sanitizer report, None if there is no report.
+    pub fn from_output_string(s: &str) -> Option<Self> {
+        let report = crash_report::parse(s)?;
+        let error = format!("{:?}: {}", report.sanitizer, report.bug_type);
+        let config = FuzzerConfig::get();
+        let frames = report.bucket_frames(
+            config.crash_frames(),
+            config.skip_runtime_frames(),
+            config.crash_frame_key(),
+        );
         let mut hasher = Sha256::new();
-        for i in &stacks {
-            hasher.update(i);
+        for frame in frames {
+            hasher.update(frame);
         }
-        hasher.update(&summary);
         hasher.update(&error);
-        let stack_hash_raw = hasher.finalize();
         let mut stack_hash = [0_u8; 32];
-        let mut stack_hash_raw_iter = stack_hash_raw.iter();
-        for i in 0..32 {
-            stack_hash[i] = stack_hash_raw_iter.nth(i).cloned().unwrap_or_default();
+        stack_hash.copy_from_slice(&hasher.finalize());
+        Some(Self { error, stack_hash })
+    }
+
+    /// For crashes without a sanitizer report, from how the program ended
+    /// and the hash of its path (`Branches::path_hash`).
+    pub fn from_status(status: &ExecStatus, path_hash: [u8; 32]) -> Self {
+        let error = match (status.signal, status.exit_code) {
+            (Some(signal), _) => format!("signal {}", signal),
+            (None, Some(exit_code)) => format!("exit {}", exit_code),
+            (None, None) => String::new(),
+        };
+        let mut hasher = Sha256::new();
+        hasher.update(path_hash);
+        hasher.update(&error);
+        let mut stack_hash = [0_u8; 32];
+        stack_hash.copy_from_slice(&hasher.finalize());
+        Self { error, stack_hash }
+    }
+
+    /// For hangs, from the functions on the stack that the sanitizer prints at
+    /// SIGABRT, since the addresses vary with where in the loop the program is.
+    /// Without a report, from the hash of the path.
+    pub fn from_hang_report(s: &str, path_hash: [u8; 32]) -> Self {
+        let error = "timeout".to_string();
+        let mut hasher = Sha256::new();
+        match crash_report::parse(s) {
+            Some(report) => {
+                let config = FuzzerConfig::get();
+                for frame in report.bucket_frames(
+                    config.crash_frames(),
+                    config.skip_runtime_frames(),
+                    CrashFrameKey::Function,
+                ) {
+                    hasher.update(frame);
+                }
+            },
+            None => hasher.update(path_hash),
         }
+        hasher.update(&error);
+        let mut stack_hash = [0_u8; 32];
+        stack_hash.copy_from_slice(&hasher.finalize());
         Self { error, stack_hash }
     }
+
+    /// Also the name of the directory of the bucket in `crashes/`.
+    pub fn bucket_hash(&self) -> String {
+        self.stack_hash[..8]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    pub fn error(&self) -> &str {
+        &self.error
+    }
 }
 
-struct Crashes {
-    inner: HashSet<CrashInfo>,
+// Written to a temporary file first, as the maps are.
+fn save_known(known: &Mutex<HashSet<CrashInfo>>, path: &Path) {
+    let buf = {
+        let lock = known.lock().unwrap();
+        let known: Vec<&CrashInfo> = lock.iter().collect();
+        serde_json::to_string(&known).expect("Could not serialize!")
+    };
+    let tmp = path.with_extension("tmp");
+    if let Err(e) = fs::write(&tmp, buf).and_then(|_| fs::rename(&tmp, path)) {
+        warn!("Could not save {:?}: {:?}", path, e);
+    }
 }
 
-impl Crashes {
-    pub fn new() -> Self {
-        Self {
-            inner: HashSet::new(),
-        }
+fn load_known(known: &Mutex<HashSet<CrashInfo>>, path: &Path) {
+    match fs::read(path).map(|buf| serde_json::from_slice::<Vec<CrashInfo>>(&buf)) {
+        Ok(Ok(infos)) => {
+            info!("Load {} known buckets from {:?}.", infos.len(), path);
+            known.lock().unwrap().extend(infos);
+        },
+        Ok(Err(e)) => warn!("Could not parse {:?}: {:?}", path, e),
+        Err(e) => warn!("Could not load {:?}: {:?}", path, e),
     }
 }
 
 pub struct GlobalBranches {
-    virgin_branches: RwLock<Trace>,
-    tmouts_branches: RwLock<Trace>,
-    crashes_branches: RwLock<Trace>,
-    crashes: RwLock<Crashes>,
+    virgin_branches: VirginMap,
+    tmouts_branches: VirginMap,
+    crashes_branches: VirginMap,
+    ooms_branches: VirginMap,
+    // Cleared for the branches whose counts vary between runs of the same input.
+    volatile_branches: VirginMap,
+    num_volatile: AtomicUsize,
+    // Crashes are rare, so a lock is fine.
+    crashes: Mutex<HashSet<CrashInfo>>,
+    hangs: Mutex<HashSet<CrashInfo>>,
     density: AtomicUsize,
 }
 
 impl GlobalBranches {
     pub fn new() -> Self {
         Self {
-            virgin_branches: RwLock::new(Trace::empty()),
-            tmouts_branches: RwLock::new(Trace::empty()),
-            crashes_branches: RwLock::new(Trace::empty()),
-            crashes: RwLock::new(Crashes::new()),
+            virgin_branches: VirginMap::new(),
+            tmouts_branches: VirginMap::new(),
+            crashes_branches: VirginMap::new(),
+            ooms_branches: VirginMap::new(),
+            volatile_branches: VirginMap::new(),
+            num_volatile: AtomicUsize::new(0),
+            crashes: Mutex::new(HashSet::new()),
+            hangs: Mutex::new(HashSet::new()),
             density: AtomicUsize::new(0),
         }
     }
@@ -179,171 +355,234 @@ impl GlobalBranches {
         let d = self.density.load(Ordering::Relaxed);
         (d * 10000 / BRANCHES_SIZE) as f32 / 100.0
     }
+
+    /// The percentage of covered branches whose counts do not vary between runs.
+    pub fn get_stability(&self) -> f32 {
+        let d = self.density.load(Ordering::Relaxed);
+        let v = self.num_volatile.load(Ordering::Relaxed);
+        match (d.saturating_sub(v) * 10000).checked_div(d) {
+            Some(s) => s as f32 / 100.0,
+            None => 100.0,
+        }
+    }
+
+    /// Whether an input in the queue has hit branch `i`.
+    pub fn is_covered(&self, i: usize) -> bool {
+        i < self.virgin_branches.len() && self.virgin_branches.get(i) != 255
+    }
+
+    fn is_volatile(&self, i: usize) -> bool {
+        self.volatile_branches.get(i) != 255
+    }
+
+    fn mark_volatile(&self, i: usize) {
+        if self.volatile_branches.clear(i, 255) == 255 {
+            self.num_volatile.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn maps(&self) -> [(&str, &VirginMap); 5] {
+        [
+            ("virgin_branches", &self.virgin_branches),
+            ("tmouts_branches", &self.tmouts_branches),
+            ("crashes_branches", &self.crashes_branches),
+            ("ooms_branches", &self.ooms_branches),
+            ("volatile_branches", &self.volatile_branches),
+        ]
+    }
+
+    /// Save the maps and the known crashes and hangs in `dir`, to resume from later.
+    pub fn save(&self, dir: &Path) {
+        for (name, map) in &self.maps() {
+            map.save(&dir.join(name));
+        }
+        save_known(&self.crashes, &dir.join(defs::KNOWN_CRASHES_FILE));
+        save_known(&self.hangs, &dir.join(defs::KNOWN_HANGS_FILE));
+    }
+
+    /// Load what `save` saved in `dir`, before the old queue is rerun,
+    /// except for the map of normal inputs (`load_virgin`). Otherwise the queue
+    /// would have nothing new, and would not be saved into the new output directory.
+    pub fn load(&self, dir: &Path) {
+        for (name, map) in &self.maps()[1..] {
+            if let Some(buf) = map.load(&dir.join(name)) {
+                for (i, &v) in buf.iter().enumerate() {
+                    map.clear(i, !v);
+                }
+            }
+        }
+        let num_volatile = (0..self.volatile_branches.len())
+            .filter(|&i| self.is_volatile(i))
+            .count();
+        self.num_volatile.store(num_volatile, Ordering::Relaxed);
+        load_known(&self.crashes, &dir.join(defs::KNOWN_CRASHES_FILE));
+        load_known(&self.hangs, &dir.join(defs::KNOWN_HANGS_FILE));
+    }
+
+    /// Add the branches that inputs saved in `dir` had covered, after the queue is rerun.
+    /// These also include inputs that were lost, e.g., because the program is not stable.
+    pub fn load_virgin(&self, dir: &Path) {
+        let map = &self.virgin_branches;
+        if let Some(buf) = map.load(&dir.join("virgin_branches")) {
+            let mut num_new_edge = 0;
+            for (i, &v) in buf.iter().enumerate() {
+                if map.clear(i, !v) == 255 && v != 255 {
+                    num_new_edge += 1;
+                }
+            }
+            self.density.fetch_add(num_new_edge, Ordering::Relaxed);
+        }
+    }
 }
 
 pub struct Branches {
     global: Arc<GlobalBranches>,
     trace: SharedMemory,
+    // Reused by `has_new` for the branches it finds.
+    new_branches: Vec<(usize, u8)>,
+    // The path of an input being calibrated, and of its rerun.
+    calibrated_path: Vec<(usize, u8)>,
+    rerun_path: Vec<(usize, u8)>,
 }
 
 impl Branches {
     pub fn new<S: AsRef<Path>>(global: Arc<GlobalBranches>, shm_name: S) -> Self {
         let trace = SharedMemory::create_empty(shm_name).expect("Could not open shared memory");
-        Self { global, trace }
+        Self {
+            global,
+            trace,
+            new_branches: Vec::new(),
+            calibrated_path: Vec::new(),
+            rerun_path: Vec::new(),
+        }
     }
 
     pub fn clear_trace(&mut self) {
         self.trace.clear();
     }
 
-    pub fn resize(&mut self) {
+    pub fn resize(&mut self) -> Result<(), String> {
         self.trace.resize().unwrap();
         // Since branch counting is using u16 to do hit count,
         // we need to divide the size by 2.
         debug_assert!((self.trace.size() & 1) == 0);
         let new_size = self.trace.size() >> 1;
         // debug!("Resized branch buffer size = {}", new_size);
-        if let Ok(mut lock) = self.global.virgin_branches.write() {
-            lock.resize(new_size);
-        }
-        if let Ok(mut lock) = self.global.tmouts_branches.write() {
-            lock.resize(new_size);
-        }
-        if let Ok(mut lock) = self.global.crashes_branches.write() {
-            lock.resize(new_size);
+        for (_, map) in &self.global.maps() {
+            map.resize(new_size)?;
         }
+        Ok(())
     }
 
-    fn get_path(&mut self) -> Vec<(usize, u8)> {
-        let mut path = Vec::<(usize, u8)>::new();
+    /// Hash of the edges the last execution hit, ignoring the hit counts,
+    /// which vary between runs that crash in the same way.
+    pub fn path_hash(&mut self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
         let table = BranchCountTable::new(&mut self.trace);
-        // debug!("Branch table = {:?}", &table);
-        let buf: &[TableEntryType] = table.branch_table;
-        for (i, &v) in buf.iter().enumerate() {
-            let c = if v > 255 { 255 } else { v as u8 };
-            #[cfg(feature = "unstable")]
-            {
-                if unsafe { unlikely(c > 0) } {
-                    path.push((i, COUNT_LOOKUP[c as usize]));
-                }
+        let global = &self.global;
+        for_each_hit(table.branch_table, |i, _| {
+            if !global.is_volatile(i) {
+                hasher.update(i.to_le_bytes());
             }
-            #[cfg(not(feature = "unstable"))]
-            {
-                if c > 0 {
-                    path.push((i, COUNT_LOOKUP[c as usize]));
-                }
-            }
-        }
+        });
+        let mut hash = [0_u8; 32];
+        hash.copy_from_slice(&hasher.finalize());
+        hash
+    }
 
-        /* let buf_plus: &[u64] = {cast!(&*table.branch_table)};
-        debug!("Bufplus = {:?}", &buf_plus);
-        for (i, &v) in buf_plus.iter().enumerate() {
-            macro_rules! run_loop {
-                () => {{
-                    let base = i * ENTRY_SIZE;
-                    for j in 0..ENTRY_SIZE {
-                        let idx = base + j;
-                        let new_val = buf[idx] as u8;
-                        if new_val > 0 {
-                            path.push((idx, COUNT_LOOKUP[new_val as usize]))
-                        }
-                    }
-                }};
-            }
-            #[cfg(feature = "unstable")]
-            {
-                if unsafe { unlikely(v > 0) } {
-                    run_loop!()
-                }
-            }
-            #[cfg(not(feature = "unstable"))]
-            {
-                if v > 0 {
-                    run_loop!()
-                }
-            }
-        } */
-        // debug!("count branch table: {}", path.len());
+    /// The branches the last execution hit, with their hit count buckets.
+    pub fn get_path(&mut self) -> Vec<(usize, u8)> {
+        let table = BranchCountTable::new(&mut self.trace);
+        let mut path = vec![];
+        for_each_hit(table.branch_table, |i, bucket| path.push((i, bucket)));
         path
     }
 
-    pub fn dedup_crash(&mut self, crash_output: &str) -> (bool, bool, usize) {
-        let crash_info = CrashInfo::from_output_string(crash_output);
-        let mut new_crash = false;
-        // There is a race condition here?!
-        match self.global.crashes.read() {
-            Ok(lock) => {
-                if !lock.inner.contains(&crash_info) {
-                    new_crash = true;
-                }
-            }
-            Err(poisoned) => warn!("Lock poisoned! {:?}", poisoned),
+    /// Remember the path of the last execution, to compare its reruns
+    /// with in `mark_volatile`.
+    pub fn start_calibration(&mut self) {
+        let table = BranchCountTable::new(&mut self.trace);
+        let path = &mut self.calibrated_path;
+        path.clear();
+        for_each_hit(table.branch_table, |i, bucket| path.push((i, bucket)));
+    }
+
+    /// Mark the branches whose buckets in the last execution, a rerun of
+    /// the calibrated input, are not the same as before as volatile.
+    pub fn mark_volatile(&mut self) {
+        let table = BranchCountTable::new(&mut self.trace);
+        let rerun = &mut self.rerun_path;
+        rerun.clear();
+        for_each_hit(table.branch_table, |i, bucket| rerun.push((i, bucket)));
+
+        let global = &self.global;
+        for_each_diff(&self.calibrated_path, rerun, |i| global.mark_volatile(i));
+    }
+
+    pub fn dedup_crash(&mut self, crash_info: CrashInfo) -> (bool, bool, usize) {
+        let new_crash = match self.global.crashes.lock() {
+            Ok(mut lock) => lock.insert(crash_info),
+            Err(poisoned) => {
+                warn!("Lock poisoned! {:?}", poisoned);
+                false
+            },
         };
-        if new_crash {
-            match self.global.crashes.write() {
-                Ok(mut lock) => {
-                    lock.inner.insert(crash_info);
-                }
-                Err(poisoned) => warn!("Lock poisoned! {:?}", poisoned),
-            }
-        }
         (new_crash, new_crash, 1)
     }
 
+    /// Whether a confirmed hang is the first one at its location.
+    pub fn dedup_hang(&mut self, hang_info: CrashInfo) -> bool {
+        match self.global.hangs.lock() {
+            Ok(mut lock) => lock.insert(hang_info),
+            Err(poisoned) => {
+                warn!("Lock poisoned! {:?}", poisoned);
+                false
+            },
+        }
+    }
+
     pub fn has_new(&mut self, status: StatusType) -> (bool, bool, usize) {
-        let path = self.get_path();
         let gb_map = match status {
             StatusType::Normal => &self.global.virgin_branches,
             StatusType::Timeout => &self.global.tmouts_branches,
             StatusType::Crash => &self.global.crashes_branches,
+            StatusType::Oom => &self.global.ooms_branches,
             _ => {
                 return (false, false, 0);
-            }
+            },
         };
-        let edge_num = path.len();
+        let table = BranchCountTable::new(&mut self.trace);
+        let len = table.branch_table.len().min(gb_map.len());
 
-        let mut to_write = vec![];
-        let mut has_new_edge = false;
-        let mut num_new_edge = 0;
-        {
-            // read only
-            let gb_map_read = gb_map.read().unwrap();
-            for &br in &path {
-                let gb_v = gb_map_read[br.0];
+        let edge_num =
+            find_new_branches(&table.branch_table[..len], gb_map, &mut self.new_branches);
+        let global = &self.global;
+        self.new_branches.retain(|&(i, _)| !global.is_volatile(i));
+        if self.new_branches.is_empty() {
+            return (false, false, edge_num);
+        }
 
+        let mut has_new_path = false;
+        let mut num_new_edge = 0;
+        for &(i, bucket) in &self.new_branches {
+            // Another executor may have covered it meanwhile.
+            let gb_v = gb_map.clear(i, bucket);
+            if (bucket & gb_v) > 0 {
                 if gb_v == 255u8 {
                     num_new_edge += 1;
                 }
-
-                if (br.1 & gb_v) > 0 {
-                    to_write.push((br.0, gb_v & (!br.1)));
-                }
+                has_new_path = true;
             }
         }
 
-        if num_new_edge > 0 {
-            if status == StatusType::Normal {
-                // only count virgin branches
-                self.global
-                    .density
-                    .fetch_add(num_new_edge, Ordering::Relaxed);
-            }
-            has_new_edge = true;
+        if num_new_edge > 0 && status == StatusType::Normal {
+            // only count virgin branches
+            self.global
+                .density
+                .fetch_add(num_new_edge, Ordering::Relaxed);
         }
 
-        if to_write.is_empty() {
-            return (false, false, edge_num);
-        }
-
-        {
-            // write
-            let mut gb_map_write = gb_map.write().unwrap();
-            for &br in &to_write {
-                gb_map_write[br.0] = br.1;
-            }
-        }
-
-        (true, has_new_edge, edge_num)
+        (has_new_path, num_new_edge > 0, edge_num)
     }
 }
 
@@ -352,3 +591,109 @@ impl std::fmt::Debug for Branches {
         write!(f, "")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    #[test]
+    fn test_find_new_branches() {
+        let mut table = vec![0_u16; 100];
+        let virgin = VirginMap::new();
+        virgin.resize(100).unwrap();
+        table[3] = 1;
+        table[40] = 300;
+        virgin.clear(3, 1);
+        let mut new = vec![];
+        assert_eq!(find_new_branches(&table, &virgin, &mut new), 2);
+        assert_eq!(new, vec![(40, 128)]);
+        assert_eq!(virgin.clear(40, 128), 255);
+        assert_eq!(virgin.clear(40, 128), 127);
+        find_new_branches(&table, &virgin, &mut new);
+        assert!(new.is_empty());
+    }
+
+    #[test]
+    fn test_diff_paths() {
+        let mut diff = vec![];
+        for_each_diff(&[(1, 1), (5, 2), (7, 1)], &[(1, 1), (5, 4), (6, 1)], |i| {
+            diff.push(i)
+        });
+        assert_eq!(diff, vec![5, 6, 7]);
+    }
+
+    #[test]
+    fn test_resize() {
+        let virgin = VirginMap::new();
+        assert!(virgin.resize(0).is_ok());
+        assert!(virgin.is_empty());
+        assert!(virgin.resize(100).is_ok());
+        assert!(virgin.resize(50).is_ok());
+        assert_eq!(virgin.len(), 100);
+    }
+
+    #[test]
+    fn test_resize_grows() {
+        let virgin = VirginMap::new();
+        virgin.resize(100).unwrap();
+        assert!(virgin.resize(200).is_err());
+        assert_eq!(virgin.len(), 100);
+    }
+
+    #[test]
+    fn test_clear_once() {
+        let virgin = Arc::new(VirginMap::new());
+        virgin.resize(1000).unwrap();
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let virgin = virgin.clone();
+                std::thread::spawn(move || {
+                    (0..1000).filter(|&i| virgin.clear(i, 1) & 1 > 0).count()
+                })
+            })
+            .collect();
+        let num_new: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(num_new, 1000);
+    }
+
+    #[test]
+    fn test_save_load() {
+        let dir = env::temp_dir().join(format!("angora_branches_test_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let new_global = || {
+            let global = GlobalBranches::new();
+            for (_, map) in &global.maps() {
+                map.resize(16).unwrap();
+            }
+            global
+        };
+
+        let global = new_global();
+        global.virgin_branches.clear(1, 1);
+        global.crashes_branches.clear(2, 255);
+        let crash =
+            CrashInfo::from_status(&ExecStatus::from_wait_status(libc::SIGSEGV, false), [0; 32]);
+        global.crashes.lock().unwrap().insert(crash);
+        global
+            .hangs
+            .lock()
+            .unwrap()
+            .insert(CrashInfo::from_hang_report("", [1; 32]));
+        global.save(&dir);
+        let tmp = dir.join(defs::KNOWN_CRASHES_FILE).with_extension("tmp");
+        assert!(!tmp.exists());
+
+        let resumed = new_global();
+        resumed.load(&dir);
+        assert_eq!(resumed.crashes_branches.get(2), 0);
+        assert_eq!(resumed.virgin_branches.get(1), 255);
+        assert_eq!(resumed.crashes.lock().unwrap().len(), 1);
+        assert_eq!(resumed.hangs.lock().unwrap().len(), 1);
+        resumed.virgin_branches.clear(3, 2);
+        resumed.load_virgin(&dir);
+        assert_eq!(resumed.virgin_branches.get(1), 254);
+        assert_eq!(resumed.virgin_branches.get(3), 253);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
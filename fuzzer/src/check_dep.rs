@@ -1,4 +1,5 @@
 use crate::command::CommandOpt;
+use angora_common::defs;
 use memmap;
 use std::{fs::File, io::prelude::*, path::Path};
 use twoway;
@@ -43,6 +44,21 @@ fn containt_string(f_data: &memmap::Mmap, s: &str) -> bool {
     twoway::find_bytes(&f_data[..], s.as_bytes()).is_some()
 }
 
+pub fn check_shm_input(target: &str) -> bool {
+    let f_data = mmap_file(target);
+    containt_string(&f_data, defs::SHM_INPUT_SIG)
+}
+
+pub fn check_track_forksrv(target: &str) -> bool {
+    let f_data = mmap_file(target);
+    containt_string(&f_data, defs::TRACK_FORKSRV_SIG)
+}
+
+pub fn check_san_forksrv(target: &str) -> bool {
+    let f_data = mmap_file(target);
+    containt_string(&f_data, defs::SAN_FORKSRV_SIG)
+}
+
 pub fn check_asan(target: &str) -> bool {
     let f_data = mmap_file(target);
     containt_string(&f_data, "libasan.so") || containt_string(&f_data, "__msan_init")
@@ -56,6 +72,13 @@ fn check_fast(target: &str) {
     }
 }
 
+fn check_persistent(target: &str) {
+    let f_data = mmap_file(target);
+    if containt_string(&f_data, defs::PERSIST_SIG) {
+        info!("Persistent mode binary detected, the target loops inside one child.");
+    }
+}
+
 fn check_track_llvm(target: &str) {
     check_target_binary(target);
     let f_data = mmap_file(target);
@@ -82,7 +105,9 @@ fn check_io_dir(in_dir: &str, out_dir: &str) {
 pub fn check_dep(in_dir: &str, out_dir: &str, cmd: &CommandOpt) {
     check_io_dir(in_dir, out_dir);
     check_crash_handling();
-    check_fast(&cmd.main.0);
+    let fast_bin = cmd.libfuzzer.as_ref().unwrap_or(&cmd.main.0);
+    check_fast(fast_bin);
+    check_persistent(fast_bin);
     if !cmd.mode.is_pin_mode() {
         check_track_llvm(&cmd.track.0);
     }
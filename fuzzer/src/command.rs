@@ -1,6 +1,18 @@
-use crate::{check_dep, search, tmpfs};
-use angora_common::defs;
+use crate::{
+    check_dep,
+    executor::{
+        cgroup,
+        net::{NetOpt, NetProto},
+    },
+    search, tmpfs,
+};
+use angora_common::{
+    config::{self, CrashFrameKey, FuzzerConfig},
+    defs,
+};
 use std::{
+    collections::HashMap,
+    convert::TryFrom,
     env, fs,
     os::unix::fs::MetadataExt,
     path::{Path, PathBuf},
@@ -10,8 +22,21 @@ use std::{
 static TMP_DIR: &str = "tmp";
 static INPUT_FILE: &str = "cur_input";
 static FORKSRV_SOCKET_FILE: &str = "forksrv_socket";
+static TRACK_FORKSRV_SOCKET_FILE: &str = "track_forksrv_socket";
+static SAN_FORKSRV_SOCKET_FILE: &str = "san_forksrv_socket";
+static SAN_STDERR_FILE: &str = "san_stderr";
 static TRACK_FILE: &str = "track";
 static PIN_ROOT_VAR: &str = "PIN_ROOT";
+static LIBFUZZER_LOADER: &str = "libfuzzer_loader";
+// Substituted with the port of each job, which then have a port of their own.
+static NET_PORT_ARG: &str = "@@PORT";
+// The variables that rerunning the sanitized program by hand needs.
+static SAN_CMDLINE_VARS: [&str; 4] = [
+    defs::ASAN_OPTIONS_VAR,
+    defs::MSAN_OPTIONS_VAR,
+    defs::UBSAN_OPTIONS_VAR,
+    defs::LD_LIBRARY_PATH_VAR,
+];
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum InstrumentationMode {
@@ -33,6 +58,26 @@ impl InstrumentationMode {
     }
 }
 
+/// "auto", "<n>ms", "<n>s" or "<n>" seconds, in ms. None for auto.
+pub fn parse_time_limit(time_limit: &str) -> Result<Option<u64>, String> {
+    let invalid = || format!("Invalid time limit: {:?}", time_limit);
+    let parse = |n: &str, unit: u64| {
+        n.parse::<u64>()
+            .ok()
+            .and_then(|n| n.checked_mul(unit))
+            .filter(|&ms| ms > 0)
+            .ok_or_else(invalid)
+    };
+    if time_limit == "auto" {
+        Ok(None)
+    } else if let Some(ms) = time_limit.strip_suffix("ms") {
+        parse(ms, 1).map(Some)
+    } else {
+        let s = time_limit.strip_suffix('s').unwrap_or(time_limit);
+        parse(s, 1000).map(Some)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CommandOpt {
     pub mode: InstrumentationMode,
@@ -43,11 +88,25 @@ pub struct CommandOpt {
     pub tmp_dir: PathBuf,
     pub out_file: String,
     pub forksrv_socket_path: String,
+    pub track_forksrv_socket_path: String,
+    pub san_forksrv_socket_path: String,
+    pub san_stderr_path: String,
     pub track_path: String,
     pub is_stdin: bool,
+    pub shm_input: bool,
+    // Send inputs over the network instead of a file or stdin.
+    pub net: Option<NetOpt>,
+    // The libFuzzer harness that `main` loads, if any.
+    pub libfuzzer: Option<String>,
+    pub track_forksrv: bool,
+    pub san_forksrv: bool,
     pub search_method: search::SearchMethod,
     pub mem_limit: u64,
+    // Limit memory with cgroups in here, instead of RLIMIT_AS.
+    pub cgroup: Option<PathBuf>,
+    // ms
     pub time_limit: u64,
+    pub is_auto_time_limit: bool,
     pub is_raw: bool,
     pub uses_asan: bool,
     pub ld_library: String,
@@ -62,7 +121,10 @@ impl CommandOpt {
         out_dir: &Path,
         search_method: &str,
         mut mem_limit: u64,
-        time_limit: u64,
+        time_limit: &str,
+        net: Option<&str>,
+        libfuzzer: bool,
+        cgroup: bool,
     ) -> Self {
         let mode = InstrumentationMode::from(mode);
 
@@ -75,9 +137,28 @@ impl CommandOpt {
             .to_str()
             .unwrap()
             .to_owned();
+        let track_forksrv_socket_path = tmp_dir
+            .join(TRACK_FORKSRV_SOCKET_FILE)
+            .to_str()
+            .unwrap()
+            .to_owned();
+        let san_forksrv_socket_path = tmp_dir
+            .join(SAN_FORKSRV_SOCKET_FILE)
+            .to_str()
+            .unwrap()
+            .to_owned();
+        let san_stderr_path = tmp_dir.join(SAN_STDERR_FILE).to_str().unwrap().to_owned();
 
         let track_path = tmp_dir.join(TRACK_FILE).to_str().unwrap().to_owned();
 
+        // Checked by the command line parser.
+        let time_limit = parse_time_limit(time_limit).expect("Could not parse time limit.");
+        let (time_limit, is_auto_time_limit) = match time_limit {
+            Some(t) => (t, false),
+            None => (config::TIME_LIMIT_CAL_MAX, true),
+        };
+        assert!(time_limit > 0, "Time limit should be positive.");
+
         let has_input_arg = pargs.contains(&"@@".to_string());
 
         let clang_lib = Command::new("llvm-config")
@@ -98,10 +179,44 @@ impl CommandOpt {
         let main_bin = tmp_args[0].clone();
         let main_args: Vec<String> = tmp_args.drain(1..).collect();
         let uses_asan = check_dep::check_asan(&main_bin);
-        if uses_asan && mem_limit != 0 {
+        // The harness is a shared object, and the loader next to the fuzzer runs it.
+        let (libfuzzer, main_bin, loader_args) = if libfuzzer {
+            let loader = env::current_exe()
+                .expect("Could not find the fuzzer binary")
+                .with_file_name(LIBFUZZER_LOADER)
+                .to_str()
+                .unwrap()
+                .to_owned();
+            let mut loader_args = vec![main_bin.clone()];
+            loader_args.extend(main_args.clone());
+            (Some(main_bin), loader, loader_args)
+        } else {
+            (None, main_bin, main_args.clone())
+        };
+        let cgroup = if cgroup && mem_limit != 0 {
+            let base = cgroup::init();
+            match base {
+                Some(ref base) => info!("Limit memory with cgroups in {:?}", base),
+                None => warn!("cgroup v2 is not writable, limit memory with rlimit"),
+            }
+            base
+        } else {
+            None
+        };
+        // RLIMIT_AS breaks ASAN, which reserves a large shadow memory.
+        if uses_asan && mem_limit != 0 && cgroup.is_none() {
             warn!("The program compiled with ASAN, set MEM_LIMIT to 0 (unlimited)");
             mem_limit = 0;
         }
+        let net = net.map(NetOpt::parse);
+        if let Some(net) = net {
+            info!("Deliver inputs over the network: {:?}", net);
+        }
+        let shm_input =
+            net.is_none() && (libfuzzer.is_some() || check_dep::check_shm_input(&main_bin));
+        if shm_input {
+            info!("The program reads __ANGORA_FUZZ_TESTCASE_BUF, deliver inputs by shared memory");
+        }
 
         let track_bin;
         let mut track_args = Vec::<String>::new();
@@ -129,8 +244,16 @@ impl CommandOpt {
             track_bin = track_target.to_string();
             track_args = main_args.clone();
         }
+        let track_forksrv = !mode.is_pin_mode() && check_dep::check_track_forksrv(&track_bin);
+        if track_forksrv {
+            info!("The track program supports forkserver");
+        }
         let san_bin = san_target.to_string();
         let san_args = main_args.clone();
+        let san_forksrv = check_dep::check_san_forksrv(&san_bin);
+        if san_forksrv {
+            info!("The sanitized program supports forkserver");
+        }
 
         for bin in [&main_bin, &track_bin, &san_bin].iter() {
             match fs::metadata(bin) {
@@ -145,27 +268,102 @@ impl CommandOpt {
         Self {
             mode,
             id: 0,
-            main: (main_bin, main_args),
+            main: (main_bin, loader_args),
             track: (track_bin, track_args),
             san: (san_bin, san_args),
             tmp_dir,
             out_file: out_file,
             forksrv_socket_path,
+            track_forksrv_socket_path,
+            san_forksrv_socket_path,
+            san_stderr_path,
             track_path,
-            is_stdin: !has_input_arg,
+            is_stdin: !has_input_arg && net.is_none(),
+            shm_input,
+            net,
+            libfuzzer,
+            track_forksrv,
+            san_forksrv,
             search_method: search::parse_search_method(search_method),
             mem_limit,
+            cgroup,
             time_limit,
+            is_auto_time_limit,
             uses_asan,
             is_raw: true,
             ld_library,
         }
     }
 
+    /// Set the timeout from the slowest seed execution time in the dry run.
+    pub fn calibrate_time_limit(&mut self, max_exec_us: u32) {
+        let t = (max_exec_us as u64 * config::TIME_LIMIT_CAL_RATIO).div_ceil(1000);
+        self.time_limit = t.clamp(config::TIME_LIMIT_CAL_MIN, config::TIME_LIMIT_CAL_MAX);
+        self.is_auto_time_limit = false;
+    }
+
+    pub fn track_time_limit(&self) -> u64 {
+        self.time_limit
+            .saturating_mul(config::TIME_LIMIT_TRACK_RATIO)
+            .clamp(config::TIME_LIMIT_TRACK_MIN, config::TIME_LIMIT_TRACK)
+    }
+
+    pub fn san_time_limit(&self) -> u64 {
+        self.time_limit
+            .saturating_mul(config::TIME_LIMIT_SAN_RATIO)
+            .max(config::TIME_LIMIT_SAN_MIN)
+    }
+
+    pub fn hang_time_limit(&self) -> u64 {
+        self.time_limit
+            .saturating_mul(config::TIME_LIMIT_HANG_RATIO)
+            .max(config::TIME_LIMIT_HANG_MIN)
+    }
+
+    /// Bash command that reruns the sanitized program on `input`, with the
+    /// sanitizer and library variables of `envs`, which the program is run with.
+    pub fn san_cmdline(&self, input: &Path, envs: &HashMap<String, String>) -> String {
+        let input = input.to_str().unwrap();
+        let mut words: Vec<String> = SAN_CMDLINE_VARS
+            .iter()
+            .filter_map(|var| {
+                envs.get(*var)
+                    .map(|value| format!("{}={}", var, shell_quote(value)))
+            })
+            .collect();
+        words.push(shell_quote(&self.san.0));
+        for arg in &self.san.1 {
+            if *arg == self.out_file {
+                words.push(shell_quote(input));
+            } else {
+                words.push(shell_quote(arg));
+            }
+        }
+        let mut cmdline = words.join(" ");
+        if let Some(net) = self.net {
+            cmdline = net_cmdline(&cmdline, input, &net);
+        } else if self.is_stdin {
+            cmdline.push_str(" < ");
+            cmdline.push_str(&shell_quote(input));
+        }
+        if let Some(ref harness) = self.libfuzzer {
+            cmdline.push_str(&format!(
+                " # libFuzzer harness, the fast build of which is {} {}",
+                shell_quote(&self.main.0),
+                shell_quote(harness)
+            ));
+        }
+        cmdline
+    }
+
     pub fn specify(&self, id: usize) -> Self {
         let mut cmd_opt = self.clone();
         let new_file = format!("{}_{}", &cmd_opt.out_file, id);
         let new_forksrv_socket_path = format!("{}_{}", &cmd_opt.forksrv_socket_path, id);
+        let new_track_forksrv_socket_path =
+            format!("{}_{}", &cmd_opt.track_forksrv_socket_path, id);
+        let new_san_forksrv_socket_path = format!("{}_{}", &cmd_opt.san_forksrv_socket_path, id);
+        let new_san_stderr_path = format!("{}_{}", &cmd_opt.san_stderr_path, id);
         let new_track_path = format!("{}_{}", &cmd_opt.track_path, id);
         if !self.is_stdin {
             for args in [
@@ -182,9 +380,42 @@ impl CommandOpt {
                 }
             }
         }
+        if let Some(ref mut net) = cmd_opt.net {
+            let uses_port_arg = cmd_opt.main.1.iter().any(|arg| arg.contains(NET_PORT_ARG));
+            if uses_port_arg {
+                let port = match u16::try_from(id)
+                    .ok()
+                    .and_then(|id| net.addr.port().checked_add(id))
+                {
+                    Some(port) => port,
+                    None => {
+                        error!(
+                            "FATAL: The port of job {} is past 65535, give the first job a lower port.",
+                            id
+                        );
+                        panic!();
+                    },
+                };
+                net.addr.set_port(port);
+                for args in [
+                    &mut cmd_opt.main.1,
+                    &mut cmd_opt.track.1,
+                    &mut cmd_opt.san.1,
+                ]
+                .iter_mut()
+                {
+                    for arg in args.iter_mut() {
+                        *arg = arg.replace(NET_PORT_ARG, &port.to_string());
+                    }
+                }
+            }
+        }
         cmd_opt.id = id;
         cmd_opt.out_file = new_file.to_owned();
         cmd_opt.forksrv_socket_path = new_forksrv_socket_path.to_owned();
+        cmd_opt.track_forksrv_socket_path = new_track_forksrv_socket_path.to_owned();
+        cmd_opt.san_forksrv_socket_path = new_san_forksrv_socket_path.to_owned();
+        cmd_opt.san_stderr_path = new_san_stderr_path.to_owned();
         cmd_opt.track_path = new_track_path.to_owned();
         cmd_opt.is_raw = false;
         cmd_opt
@@ -198,3 +429,131 @@ impl Drop for CommandOpt {
         }
     }
 }
+
+/// Quote `word` for a shell, unless it is made of characters that need no quotes.
+pub fn shell_quote(word: &str) -> String {
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || "_-+=/.,:@%".contains(c);
+    if !word.is_empty() && word.chars().all(is_plain) {
+        word.to_owned()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+/// `ASAN_OPTIONS`, `MSAN_OPTIONS` or `UBSAN_OPTIONS`, which only symbolize
+/// the reports if crash buckets need the function names, and only detect leaks if asked to.
+pub fn san_options(options: &str) -> String {
+    let config = FuzzerConfig::get();
+    let mut options = options.to_owned();
+    if config.crash_frame_key() == CrashFrameKey::Function {
+        options = options.replace("symbolize=0", "symbolize=1");
+    }
+    if config.detect_leaks() {
+        options = options.replace("detect_leaks=0", "detect_leaks=1");
+    }
+    options
+}
+
+/// Run `cmdline`, which listens on `net`, in the background, and send it `input`
+/// with bash once the port is ready, as the fuzzer waits for it.
+fn net_cmdline(cmdline: &str, input: &str, net: &NetOpt) -> String {
+    let port = net.addr.port();
+    // Unless the program exits first.
+    let wait_until = |cond: &str| {
+        format!(
+            "until {}; do kill -0 $! 2>/dev/null || break; sleep 0.1; done",
+            cond
+        )
+    };
+    match net.proto {
+        // Connecting fails until the program listens.
+        NetProto::Tcp => format!(
+            "{} & {}; wait",
+            cmdline,
+            wait_until(&format!(
+                "cat {} 2>/dev/null > /dev/tcp/{}/{}",
+                shell_quote(input),
+                net.addr.ip(),
+                port
+            ))
+        ),
+        // A datagram sent before the program binds the port is lost.
+        NetProto::Udp => format!(
+            "{} & {}; cat {} > /dev/udp/{}/{}; wait",
+            cmdline,
+            wait_until(&format!(
+                "grep -q '^ *[0-9]*: [0-9A-F]*:{:04X} ' /proc/net/udp /proc/net/udp6",
+                port
+            )),
+            shell_quote(input),
+            net.addr.ip(),
+            port
+        ),
+    }
+}
+
+/// Options of the rerun of a timeout, in which SIGABRT makes the sanitizer
+/// print the stack, with the function names that hangs are told apart by.
+pub fn hang_san_options(options: &str) -> String {
+    format!(
+        "{}:handle_abort=1",
+        options.replace("symbolize=0", "symbolize=1")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_time_limit() {
+        assert_eq!(parse_time_limit("auto"), Ok(None));
+        assert_eq!(parse_time_limit("2"), Ok(Some(2000)));
+        assert_eq!(parse_time_limit("3s"), Ok(Some(3000)));
+        assert_eq!(parse_time_limit("50ms"), Ok(Some(50)));
+        assert!(parse_time_limit("5sss").is_err());
+        assert!(parse_time_limit("ms").is_err());
+        assert!(parse_time_limit("fast").is_err());
+        assert!(parse_time_limit("0").is_err());
+        assert!(parse_time_limit("0ms").is_err());
+        assert!(parse_time_limit(&format!("{}s", u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(
+            shell_quote("/tmp/id:000001,sig:11"),
+            "/tmp/id:000001,sig:11"
+        );
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(
+            shell_quote("$LD_LIBRARY_PATH:/lib"),
+            "'$LD_LIBRARY_PATH:/lib'"
+        );
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn test_net_cmdline() {
+        assert_eq!(
+            net_cmdline(
+                "./server",
+                "/tmp/in",
+                &NetOpt::parse("tcp://127.0.0.1:8080")
+            ),
+            "./server & until cat /tmp/in 2>/dev/null > /dev/tcp/127.0.0.1/8080; \
+             do kill -0 $! 2>/dev/null || break; sleep 0.1; done; wait"
+        );
+        assert_eq!(
+            net_cmdline(
+                "./server",
+                "/tmp/in",
+                &NetOpt::parse("udp://127.0.0.1:8080")
+            ),
+            "./server & until grep -q '^ *[0-9]*: [0-9A-F]*:1F90 ' /proc/net/udp /proc/net/udp6; \
+             do kill -0 $! 2>/dev/null || break; sleep 0.1; done; \
+             cat /tmp/in > /dev/udp/127.0.0.1/8080; wait"
+        );
+    }
+}
@@ -0,0 +1,218 @@
+// Labels crashes by what the bug is, to rank the buckets by how bad they may be.
+use crate::{
+    crash_report::{self, Access, CrashReport, Region},
+    executor::{ExecStatus, SanitizerKind},
+};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BugClass {
+    Overflow,
+    UseAfterFree,
+    DoubleFree,
+    InvalidFree,
+    NullDeref,
+    WildAccess,
+    DivByZero,
+    IntegerOverflow,
+    StackExhaustion,
+    Uninitialized,
+    Leak,
+    Other,
+}
+
+/// An estimate, since only the report is known: writes out of bounds and
+/// misused frees are the usual ways into exploits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CrashClass {
+    pub bug: BugClass,
+    pub access: Option<Access>,
+    pub region: Option<Region>,
+    pub severity: Severity,
+}
+
+impl fmt::Display for BugClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            BugClass::Overflow => "overflow",
+            BugClass::UseAfterFree => "use-after-free",
+            BugClass::DoubleFree => "double-free",
+            BugClass::InvalidFree => "invalid-free",
+            BugClass::NullDeref => "null-deref",
+            BugClass::WildAccess => "wild-access",
+            BugClass::DivByZero => "div-by-zero",
+            BugClass::IntegerOverflow => "integer-overflow",
+            BugClass::StackExhaustion => "stack-exhaustion",
+            BugClass::Uninitialized => "uninitialized",
+            BugClass::Leak => "leak",
+            BugClass::Other => "other",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+fn asan_bug_class(report: &CrashReport) -> BugClass {
+    let bug_type = report.bug_type.as_str();
+    let description = report.description.as_str();
+    if bug_type == "stack-overflow" {
+        BugClass::StackExhaustion
+    } else if bug_type.contains("overflow") || bug_type.contains("underflow") {
+        BugClass::Overflow
+    } else if bug_type.contains("use-after") {
+        BugClass::UseAfterFree
+    } else if description.starts_with("attempting double-free") {
+        BugClass::DoubleFree
+    } else if description.starts_with("attempting free")
+        || bug_type == "bad-free"
+        || bug_type.contains("mismatch")
+    {
+        BugClass::InvalidFree
+    } else if bug_type == "SEGV" && report.zero_page {
+        BugClass::NullDeref
+    } else if bug_type == "SEGV" {
+        BugClass::WildAccess
+    } else if bug_type == "FPE" {
+        BugClass::DivByZero
+    } else {
+        BugClass::Other
+    }
+}
+
+fn ubsan_bug_class(report: &CrashReport) -> BugClass {
+    let bug_type = report.bug_type.as_str();
+    if bug_type.contains("null pointer") {
+        BugClass::NullDeref
+    } else if bug_type.contains("division by zero") {
+        BugClass::DivByZero
+    } else if bug_type.contains("integer overflow") || bug_type.starts_with("negation of") {
+        BugClass::IntegerOverflow
+    } else if bug_type.contains("out of bounds") {
+        BugClass::Overflow
+    } else {
+        BugClass::Other
+    }
+}
+
+fn region_of(report: &CrashReport) -> Option<Region> {
+    let bug_type = report.bug_type.as_str();
+    if bug_type.starts_with("heap-") || bug_type.contains("free") {
+        Some(Region::Heap)
+    } else if bug_type.starts_with("stack-") || bug_type.starts_with("dynamic-stack-") {
+        Some(Region::Stack)
+    } else if bug_type.starts_with("global-") {
+        Some(Region::Global)
+    } else {
+        report.region
+    }
+}
+
+fn severity(bug: BugClass, access: Option<Access>) -> Severity {
+    match bug {
+        BugClass::UseAfterFree | BugClass::DoubleFree | BugClass::InvalidFree => Severity::High,
+        BugClass::Overflow | BugClass::WildAccess if access != Some(Access::Read) => Severity::High,
+        BugClass::Overflow | BugClass::WildAccess | BugClass::Uninitialized | BugClass::Other => {
+            Severity::Medium
+        },
+        _ => Severity::Low,
+    }
+}
+
+impl CrashClass {
+    fn new(bug: BugClass, access: Option<Access>, region: Option<Region>) -> Self {
+        Self {
+            bug,
+            access,
+            region,
+            severity: severity(bug, access),
+        }
+    }
+
+    pub fn from_report(report: &CrashReport) -> Self {
+        let bug = match report.sanitizer {
+            SanitizerKind::Asan => asan_bug_class(report),
+            SanitizerKind::Ubsan => ubsan_bug_class(report),
+            SanitizerKind::Msan => BugClass::Uninitialized,
+            SanitizerKind::Lsan => BugClass::Leak,
+        };
+        Self::new(bug, report.access, region_of(report))
+    }
+
+    /// For crashes without a sanitizer report, from the signal alone.
+    pub fn from_status(status: &ExecStatus) -> Self {
+        let bug = match status.signal {
+            Some(libc::SIGFPE) => BugClass::DivByZero,
+            Some(libc::SIGSEGV) | Some(libc::SIGBUS) => BugClass::WildAccess,
+            _ => BugClass::Other,
+        };
+        Self::new(bug, None, None)
+    }
+
+    /// From the output of the sanitized program, or the status if it has no report.
+    pub fn classify(san_stderr: &str, status: &ExecStatus) -> Self {
+        match crash_report::parse(san_stderr) {
+            Some(report) => Self::from_report(&report),
+            None => Self::from_status(status),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let status = ExecStatus::from_wait_status(libc::SIGSEGV, false);
+        let class = CrashClass::classify(
+            "==1==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602000000011
+WRITE of size 1 at 0x602000000011 thread T0
+    #0 0x4f4a1b in parse /src/a.c:12:3",
+            &status,
+        );
+        assert_eq!(class.bug, BugClass::Overflow);
+        assert_eq!(class.access, Some(Access::Write));
+        assert_eq!(class.region, Some(Region::Heap));
+        assert_eq!(class.severity, Severity::High);
+
+        let class = CrashClass::classify(
+            "==1==ERROR: AddressSanitizer: SEGV on unknown address 0x000000000000
+==1==The signal is caused by a READ memory access.
+==1==Hint: address points to the zero page.
+    #0 0x4f4a1b in parse /src/a.c:12:3",
+            &status,
+        );
+        assert_eq!(class.bug, BugClass::NullDeref);
+        assert_eq!(class.access, Some(Access::Read));
+        assert_eq!(class.severity, Severity::Low);
+
+        let class = CrashClass::classify(
+            "==1==ERROR: AddressSanitizer: attempting double-free on 0x602000000010 in thread T0:
+    #0 0x4f4a1b in free
+0x602000000010 is located 0 bytes inside of 1-byte region [0x602000000010,0x602000000011)
+freed by thread T0 here:",
+            &status,
+        );
+        assert_eq!(class.bug, BugClass::DoubleFree);
+        assert_eq!(class.region, Some(Region::Heap));
+
+        let class = CrashClass::classify(
+            "/src/a.c:12:3: runtime error: signed integer overflow: 2147483647 + 1 cannot be represented in type 'int'",
+            &status,
+        );
+        assert_eq!(class.bug, BugClass::IntegerOverflow);
+        assert_eq!(class.severity, Severity::Low);
+
+        let class = CrashClass::classify("", &ExecStatus::from_wait_status(libc::SIGFPE, false));
+        assert_eq!(class.bug, BugClass::DivByZero);
+    }
+}
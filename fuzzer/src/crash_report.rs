@@ -0,0 +1,360 @@
+// Parsers for the reports that sanitizers print on stderr.
+use crate::executor::SanitizerKind;
+use angora_common::config::CrashFrameKey;
+use serde_derive::{Deserialize, Serialize};
+
+// Functions and modules of the sanitizer runtimes and libc, which are the same
+// for different bugs, e.g. `__interceptor_memcpy` or `abort` in libc.so.6.
+static RUNTIME_FUNCTION_PREFIXES: [&str; 7] = [
+    "__interceptor_",
+    "__asan",
+    "__msan",
+    "__lsan",
+    "__ubsan",
+    "__sanitizer",
+    "__libc_",
+];
+static RUNTIME_LOCATIONS: [&str; 5] = [
+    "compiler-rt/",
+    "/libc.so",
+    "/libc-",
+    "/libstdc++.so",
+    "/libc++",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackFrame {
+    // Only if the report is symbolized.
+    pub function: Option<String>,
+    // "file.c:12:3", or "(module+0x4f4a1b)" if there is no debug info.
+    pub location: String,
+}
+
+impl StackFrame {
+    /// From "    #0 0x4f4a1b in func file.c:12:3" or "    #0 0x4f4a1b  (module+0x4f4a1b)".
+    fn parse(line: &str) -> Option<Self> {
+        if !line.trim_start().starts_with('#') {
+            return None;
+        }
+        let location = line.split_whitespace().last()?.to_owned();
+        let function = line.find(" in ").and_then(|i| {
+            let rest = &line[i + 4..];
+            // C++ names have spaces in them.
+            let function = match rest.rfind(' ') {
+                Some(j) => &rest[..j],
+                None => rest,
+            };
+            Some(function.trim().to_owned()).filter(|f| !f.is_empty())
+        });
+        Some(Self { function, location })
+    }
+
+    pub fn is_runtime(&self) -> bool {
+        self.function.as_ref().is_some_and(|f| {
+            RUNTIME_FUNCTION_PREFIXES
+                .iter()
+                .any(|prefix| f.starts_with(prefix))
+        }) || RUNTIME_LOCATIONS
+            .iter()
+            .any(|loc| self.location.contains(loc))
+    }
+
+    pub fn key(&self, key: CrashFrameKey) -> &str {
+        match (key, &self.function) {
+            (CrashFrameKey::Function, Some(function)) => function,
+            _ => &self.location,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// Where the memory that a bad access hits is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Region {
+    Heap,
+    Stack,
+    Global,
+}
+
+/// What a report says about a crash.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrashReport {
+    pub sanitizer: SanitizerKind,
+    pub bug_type: String,
+    // The rest of the first line of the report, after the name of the sanitizer.
+    pub description: String,
+    // Innermost first.
+    pub frames: Vec<StackFrame>,
+    pub access: Option<Access>,
+    pub region: Option<Region>,
+    // ASan hints at NULL dereferences with "address points to the zero page".
+    pub zero_page: bool,
+}
+
+impl CrashReport {
+    /// Keys of the top `num` frames that tell this bug from others, all of them if `num` is 0.
+    pub fn bucket_frames(&self, num: usize, skip_runtime: bool, key: CrashFrameKey) -> Vec<&str> {
+        let num = if num == 0 { self.frames.len() } else { num };
+        let frames: Vec<&StackFrame> = self
+            .frames
+            .iter()
+            .filter(|frame| !(skip_runtime && frame.is_runtime()))
+            .collect();
+        // A crash inside libc called from main() has nothing else left.
+        let frames = if frames.is_empty() {
+            self.frames.iter().collect()
+        } else {
+            frames
+        };
+        frames
+            .iter()
+            .take(num)
+            .map(|frame| frame.key(key))
+            .collect()
+    }
+}
+
+pub trait ReportParser: Sync {
+    /// None if the report is not in the format of this parser.
+    fn parse(&self, report: &str) -> Option<CrashReport>;
+}
+
+/// ASan, MSan and LSan start with "==PID==ERROR: NameSanitizer: bug-type ..."
+/// and print frames as "    #0 0x4f4a1b in func file.c:12:3".
+struct SanitizerParser {
+    sanitizer: SanitizerKind,
+    name: &'static str,
+    // The header of LSan has no bug type in it.
+    bug_type: Option<&'static str>,
+}
+
+impl ReportParser for SanitizerParser {
+    fn parse(&self, report: &str) -> Option<CrashReport> {
+        let error = format!("ERROR: {}: ", self.name);
+        let warning = format!("WARNING: {}: ", self.name);
+        let mut lines = report.lines();
+        let header = lines.find(|line| line.contains(&error) || line.contains(&warning))?;
+        let bug_type = match self.bug_type {
+            Some(bug_type) => bug_type.to_owned(),
+            None => header
+                .split(&format!("{}: ", self.name))
+                .nth(1)
+                .and_then(|s| s.split_whitespace().next())
+                .unwrap_or_default()
+                .to_owned(),
+        };
+        let description = header
+            .split(&format!("{}: ", self.name))
+            .nth(1)
+            .unwrap_or_default()
+            .to_owned();
+        let body: Vec<&str> = lines
+            .take_while(|line| !line.starts_with("SUMMARY:"))
+            .collect();
+        let frames = body
+            .iter()
+            .filter_map(|line| StackFrame::parse(line))
+            .collect();
+        Some(CrashReport {
+            sanitizer: self.sanitizer,
+            bug_type,
+            description,
+            frames,
+            access: parse_access(&body),
+            region: parse_region(&body),
+            zero_page: body
+                .iter()
+                .any(|line| line.contains("points to the zero page")),
+        })
+    }
+}
+
+/// From "READ of size 4 at ..." or, for SEGV, "The signal is caused by a WRITE memory access."
+fn parse_access(body: &[&str]) -> Option<Access> {
+    body.iter().find_map(|line| {
+        let line = line.trim_start();
+        if line.starts_with("READ of size") || line.contains("caused by a READ") {
+            Some(Access::Read)
+        } else if line.starts_with("WRITE of size") || line.contains("caused by a WRITE") {
+            Some(Access::Write)
+        } else {
+            None
+        }
+    })
+}
+
+/// From the description of the address, e.g. "is located in stack of thread T0"
+/// or "is located 0 bytes to the right of 10-byte region [...)".
+fn parse_region(body: &[&str]) -> Option<Region> {
+    body.iter().find_map(|line| {
+        if line.contains("is located in stack of") {
+            Some(Region::Stack)
+        } else if line.contains("of global variable") {
+            Some(Region::Global)
+        } else if line.contains("-byte region [") || line.starts_with("freed by thread") {
+            Some(Region::Heap)
+        } else {
+            None
+        }
+    })
+}
+
+/// UBSan prints "file.c:12:3: runtime error: message", followed by frames
+/// only if UBSAN_OPTIONS has print_stacktrace=1.
+struct UbsanParser;
+
+static UBSAN_ERROR: &str = ": runtime error: ";
+
+impl ReportParser for UbsanParser {
+    fn parse(&self, report: &str) -> Option<CrashReport> {
+        let mut lines = report.lines();
+        let header = lines.find(|line| line.contains(UBSAN_ERROR))?;
+        let mut parts = header.splitn(2, UBSAN_ERROR);
+        let loc = parts.next().unwrap_or_default();
+        let msg = parts.next().unwrap_or_default();
+        // The values in "signed integer overflow: 2147483647 + 1 cannot be ..."
+        // or "index 10 out of bounds" differ for the same bug.
+        let bug_type = msg
+            .split(':')
+            .next()
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(|word| {
+                if word.bytes().any(|b| b.is_ascii_digit()) {
+                    "N"
+                } else {
+                    word
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut frames = vec![StackFrame {
+            function: None,
+            location: loc.to_owned(),
+        }];
+        frames.extend(
+            lines
+                .take_while(|line| !line.starts_with("SUMMARY:"))
+                .filter_map(StackFrame::parse),
+        );
+        Some(CrashReport {
+            sanitizer: SanitizerKind::Ubsan,
+            bug_type,
+            description: msg.to_owned(),
+            frames,
+            access: None,
+            region: None,
+            zero_page: false,
+        })
+    }
+}
+
+// Tried in order: a fatal ASan error comes after the UBSan errors it recovered from.
+static PARSERS: [&dyn ReportParser; 4] = [
+    &SanitizerParser {
+        sanitizer: SanitizerKind::Asan,
+        name: "AddressSanitizer",
+        bug_type: None,
+    },
+    &SanitizerParser {
+        sanitizer: SanitizerKind::Msan,
+        name: "MemorySanitizer",
+        bug_type: None,
+    },
+    &SanitizerParser {
+        sanitizer: SanitizerKind::Lsan,
+        name: "LeakSanitizer",
+        bug_type: Some("memory-leak"),
+    },
+    &UbsanParser,
+];
+
+/// Parse the stderr of a sanitized program, None if it has no sanitizer report.
+pub fn parse(report: &str) -> Option<CrashReport> {
+    PARSERS.iter().find_map(|parser| parser.parse(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_reports() {
+        let asan = parse(
+            "==1==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602000000011
+READ of size 1 at 0x602000000011 thread T0
+    #0 0x4f4a1b in parse /src/a.c:12:3
+    #1 0x4f4b2c in main /src/a.c:30:5
+SUMMARY: AddressSanitizer: heap-buffer-overflow /src/a.c:12:3 in parse",
+        )
+        .unwrap();
+        assert_eq!(asan.sanitizer, SanitizerKind::Asan);
+        assert_eq!(asan.bug_type, "heap-buffer-overflow");
+        assert_eq!(asan.frames[0].function.as_deref(), Some("parse"));
+        assert_eq!(asan.frames[1].location, "/src/a.c:30:5");
+        assert_eq!(asan.access, Some(Access::Read));
+
+        let msan = parse(
+            "==1==WARNING: MemorySanitizer: use-of-uninitialized-value
+    #0 0x4f4a1b in parse /src/a.c:12:3",
+        )
+        .unwrap();
+        assert_eq!(msan.sanitizer, SanitizerKind::Msan);
+        assert_eq!(msan.bug_type, "use-of-uninitialized-value");
+
+        let lsan = parse(
+            "==1==ERROR: LeakSanitizer: detected memory leaks
+
+Direct leak of 8 byte(s) in 1 object(s) allocated from:
+    #0 0x4f4a1b in malloc
+    #1 0x4f4b2c in parse /src/a.c:12:3",
+        )
+        .unwrap();
+        assert_eq!(lsan.sanitizer, SanitizerKind::Lsan);
+        assert_eq!(lsan.bug_type, "memory-leak");
+        assert_eq!(lsan.frames.len(), 2);
+
+        let ubsan = parse(
+            "/src/a.c:12:3: runtime error: signed integer overflow: 2147483647 + 1 cannot be represented in type 'int'",
+        )
+        .unwrap();
+        assert_eq!(ubsan.sanitizer, SanitizerKind::Ubsan);
+        assert_eq!(ubsan.bug_type, "signed integer overflow");
+        assert_eq!(ubsan.frames[0].location, "/src/a.c:12:3");
+
+        assert!(parse("Segmentation fault").is_none());
+    }
+
+    #[test]
+    fn test_bucket_frames() {
+        let report = parse(
+            "==1==ERROR: AddressSanitizer: stack-buffer-overflow on address 0x7ffc
+    #0 0x4c3f1d in __interceptor_strcpy /src/llvm/compiler-rt/lib/asan/asan_interceptors.cpp:437:5
+    #1 0x4f4a1b in parse(char const*, int) /src/a.cc:12:3
+    #2 0x4f4b2c in main /src/a.cc:30:5
+    #3 0x7f0a1b  (/lib/x86_64-linux-gnu/libc.so.6+0x21b96)",
+        )
+        .unwrap();
+        assert_eq!(
+            report.bucket_frames(2, true, CrashFrameKey::Function),
+            vec!["parse(char const*, int)", "main"]
+        );
+        assert_eq!(
+            report.bucket_frames(0, true, CrashFrameKey::ModuleOffset),
+            vec!["/src/a.cc:12:3", "/src/a.cc:30:5"]
+        );
+        assert_eq!(
+            report
+                .bucket_frames(0, false, CrashFrameKey::Function)
+                .len(),
+            4
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use super::*;
+use crate::cond_stmt::CondStmt;
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    cmp,
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// What a resumed campaign needs of the depot: the counters of the saved inputs,
+/// the files of the queue, and every condition with its solving state and priority.
+#[derive(Serialize, Deserialize)]
+pub struct DepotCheckpoint {
+    num_inputs: usize,
+    num_hangs: usize,
+    num_crashes: usize,
+    num_unconfirmed_crashes: usize,
+    num_ooms: usize,
+    // File names in `queue/`, which tell where the inputs come from.
+    input_names: BTreeMap<usize, String>,
+    conds: Vec<(CondStmt, u16)>,
+}
+
+impl Depot {
+    pub fn checkpoint(&self) -> DepotCheckpoint {
+        let conds = {
+            let q = match self.queue.lock() {
+                Ok(guard) => guard,
+                Err(poisoned) => {
+                    warn!("Mutex poisoned! Results may be incorrect. Continuing...");
+                    poisoned.into_inner()
+                },
+            };
+            q.iter().map(|(cond, p)| (cond.clone(), p.0)).collect()
+        };
+        let input_names = self
+            .input_paths
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|(&id, path)| Some((id, path.file_name()?.to_str()?.to_owned())))
+            .collect();
+        DepotCheckpoint {
+            num_inputs: self.num_inputs.load(Ordering::Relaxed),
+            num_hangs: self.num_hangs.load(Ordering::Relaxed),
+            num_crashes: self.num_crashes.load(Ordering::Relaxed),
+            num_unconfirmed_crashes: self.num_unconfirmed_crashes.load(Ordering::Relaxed),
+            num_ooms: self.num_ooms.load(Ordering::Relaxed),
+            input_names,
+            conds,
+        }
+    }
+
+    /// Go on from a checkpoint of this output directory, before the fuzzing threads start.
+    pub fn restore(&self, checkpoint: DepotCheckpoint) {
+        let dirs = &self.dirs;
+        let inputs = id_files(&dirs.inputs_dir);
+        restore_num(
+            &self.num_inputs,
+            checkpoint.num_inputs,
+            &inputs,
+            &dirs.inputs_dir,
+        );
+        restore_num(
+            &self.num_hangs,
+            checkpoint.num_hangs,
+            &id_files(&dirs.hangs_dir),
+            &dirs.hangs_dir,
+        );
+        restore_num(
+            &self.num_crashes,
+            checkpoint.num_crashes,
+            &crash_files(&dirs.crashes_dir),
+            &dirs.crashes_dir,
+        );
+        restore_num(
+            &self.num_unconfirmed_crashes,
+            checkpoint.num_unconfirmed_crashes,
+            &id_files(&dirs.unconfirmed_crashes_dir),
+            &dirs.unconfirmed_crashes_dir,
+        );
+        restore_num(
+            &self.num_ooms,
+            checkpoint.num_ooms,
+            &id_files(&dirs.ooms_dir),
+            &dirs.ooms_dir,
+        );
+        // Inputs saved after the checkpoint are only known by their files.
+        let mut input_paths = self.input_paths.write().unwrap();
+        input_paths.extend(inputs);
+        for (id, name) in checkpoint.input_names {
+            input_paths.insert(id, self.dirs.inputs_dir.join(name));
+        }
+        let mut q = self.queue.lock().unwrap();
+        info!("Restore {} conditions.", checkpoint.conds.len());
+        for (cond, p) in checkpoint.conds {
+            q.push(cond, QPriority(p));
+        }
+    }
+}
+
+/// Files in `dir` by their id, from names that start with `id:NNNNNN`.
+fn id_files(dir: &Path) -> BTreeMap<usize, PathBuf> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return BTreeMap::new(),
+    };
+    entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let id = path
+                .file_name()?
+                .to_str()?
+                .strip_prefix("id:")?
+                .get(..6)?
+                .parse::<usize>()
+                .ok()?;
+            Some((id, path))
+        })
+        .collect()
+}
+
+/// Crashes in every bucket of `crashes/`, which share the ids.
+fn crash_files(dir: &Path) -> BTreeMap<usize, PathBuf> {
+    let mut files = BTreeMap::new();
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            if entry.file_type().map_or(false, |t| t.is_dir()) {
+                files.append(&mut id_files(&entry.path()));
+            }
+        }
+    }
+    files
+}
+
+// Files may have been saved after the last checkpoint. Their ids must not be reused,
+// or a new crash would overwrite an old one.
+fn restore_num(num: &AtomicUsize, saved: usize, files: &BTreeMap<usize, PathBuf>, dir: &Path) {
+    let next_id = files.keys().next_back().map_or(0, |id| id + 1);
+    if next_id > saved {
+        warn!(
+            "{} more files in {:?} than in the checkpoint.",
+            next_id - saved,
+            dir
+        );
+    }
+    num.store(cmp::max(saved, next_id), Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    #[test]
+    fn test_restore_num() {
+        let dir = env::temp_dir().join(format!("angora_checkpoint_{}", process::id()));
+        // Saved after the checkpoint.
+        let bucket = dir.join("crashes").join("SIGSEGV");
+        fs::create_dir_all(&bucket).unwrap();
+        fs::write(bucket.join("id:000004"), b"crash").unwrap();
+        fs::write(bucket.join("id:000004.report"), b"report").unwrap();
+        let queue = dir.join("queue");
+        fs::create_dir_all(queue.join(".meta")).unwrap();
+        let input = queue.join("id:000002,src:000000,op:havoc");
+        fs::write(&input, b"input").unwrap();
+
+        let num = AtomicUsize::new(0);
+        restore_num(&num, 1, &crash_files(&dir.join("crashes")), &dir);
+        assert_eq!(num.load(Ordering::Relaxed), 5);
+        restore_num(&num, 7, &crash_files(&dir.join("crashes")), &dir);
+        assert_eq!(num.load(Ordering::Relaxed), 7);
+        let inputs = id_files(&queue);
+        restore_num(&num, 0, &inputs, &queue);
+        assert_eq!(num.load(Ordering::Relaxed), 3);
+        assert_eq!(inputs.into_iter().collect::<Vec<_>>(), vec![(2, input)]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{crash_class::CrashClass, executor::ExecStatus};
+use serde_derive::Serialize;
+
+/// Saved as `id:NNNNNN.json` next to each crash, to triage it without rerunning.
+#[derive(Debug, Serialize)]
+pub struct CrashBundle {
+    pub id: usize,
+    pub status: String,
+    pub signal: Option<i32>,
+    pub exit_code: Option<i32>,
+    pub sanitizer: Option<String>,
+    // Bucket of the crash, the directory it is saved in. Empty for crashes
+    // that the sanitized program does not confirm.
+    pub bucket: String,
+    pub class: CrashClass,
+    pub cmpid: u32,
+    pub command: String,
+    // Only checked for the first crash of each bucket.
+    pub reproducibility: Option<Reproducibility>,
+}
+
+/// How often the reruns of a crash crash again.
+#[derive(Debug, Serialize)]
+pub struct Reproducibility {
+    pub runs: usize,
+    // Ratios of the reruns that crashed in the fast and the sanitized programs.
+    pub fast: f32,
+    pub sanitizer: f32,
+}
+
+impl Reproducibility {
+    pub fn new(runs: usize, fast_crashes: usize, san_crashes: usize) -> Self {
+        let ratio = |crashes| match runs {
+            0 => 0.0,
+            _ => crashes as f32 / runs as f32,
+        };
+        Self {
+            runs,
+            fast: ratio(fast_crashes),
+            sanitizer: ratio(san_crashes),
+        }
+    }
+}
+
+impl CrashBundle {
+    pub fn new(
+        id: usize,
+        status: &ExecStatus,
+        bucket: String,
+        class: CrashClass,
+        cmpid: u32,
+        command: String,
+        reproducibility: Option<Reproducibility>,
+    ) -> Self {
+        Self {
+            id,
+            status: format!("{:?}", status.status),
+            signal: status.signal,
+            exit_code: status.exit_code,
+            sanitizer: status.sanitizer.map(|s| format!("{:?}", s)),
+            bucket,
+            class,
+            cmpid,
+            command,
+            reproducibility,
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use crate::crash_class::{BugClass, CrashClass, Severity};
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// A bucket of crashes with the same bug, `crashes/<bucket>/`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CrashBucket {
+    pub error: String,
+    pub class: CrashClass,
+    // Seconds since the epoch.
+    pub first_seen: u64,
+    // Crashing executions, including the ones not saved.
+    pub hits: usize,
+    pub saved: usize,
+}
+
+/// Saved as `crashes/index.json` by `save`, if a bucket has changed since.
+pub struct CrashIndex {
+    path: PathBuf,
+    buckets: Mutex<BTreeMap<String, CrashBucket>>,
+    dirty: AtomicBool,
+}
+
+impl CrashIndex {
+    /// Go on with the buckets in `path`, if a resumed campaign has saved them there.
+    pub fn new(path: PathBuf) -> Self {
+        let buckets = match fs::read(&path) {
+            Ok(buf) => serde_json::from_slice(&buf).unwrap_or_else(|e| {
+                warn!("Could not parse crash index {:?}: {:?}", path, e);
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+        Self {
+            path,
+            buckets: Mutex::new(buckets),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    pub fn hit(&self, bucket: &str, error: &str, class: CrashClass, saved: bool) {
+        let mut buckets = match self.buckets.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => {
+                warn!("Mutex poisoned! Results may be incorrect. Continuing...");
+                poisoned.into_inner()
+            },
+        };
+        let entry = buckets
+            .entry(bucket.to_owned())
+            .or_insert_with(|| CrashBucket {
+                error: error.to_owned(),
+                class,
+                first_seen: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0),
+                hits: 0,
+                saved: 0,
+            });
+        entry.hits += 1;
+        if saved {
+            entry.saved += 1;
+        }
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    /// Written to a temporary file first, so a kill in the middle leaves the last one intact.
+    pub fn save(&self) {
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        let index = match self.buckets.lock() {
+            Ok(buckets) => serde_json::to_string_pretty(&*buckets).expect("Could not serialize!"),
+            Err(_) => return,
+        };
+        let tmp = self.path.with_extension("tmp");
+        if let Err(e) = fs::write(&tmp, index).and_then(|_| fs::rename(&tmp, &self.path)) {
+            warn!("Could not save crash index: {:?}", e);
+        }
+    }
+
+    /// Buckets of each severity and of each bug class.
+    pub fn count_classes(&self) -> (BTreeMap<Severity, usize>, BTreeMap<BugClass, usize>) {
+        let mut severities = BTreeMap::new();
+        let mut bugs = BTreeMap::new();
+        if let Ok(buckets) = self.buckets.lock() {
+            for bucket in buckets.values() {
+                *severities.entry(bucket.class.severity).or_insert(0) += 1;
+                *bugs.entry(bucket.class.bug).or_insert(0) += 1;
+            }
+        }
+        (severities, bugs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::{ExecStatus, StatusType};
+    use std::{env, process};
+
+    #[test]
+    fn test_save() {
+        let dir = env::temp_dir().join(format!("angora_crash_index_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("index.json");
+        let index = CrashIndex::new(path.clone());
+        let class = CrashClass::from_status(&ExecStatus::new(StatusType::Crash));
+        index.hit("a", "SIGSEGV", class, true);
+        index.hit("a", "SIGSEGV", class, false);
+        assert!(!path.exists());
+        index.save();
+        let buckets = CrashIndex::new(path.clone()).buckets.into_inner().unwrap();
+        assert_eq!(buckets["a"].hits, 2);
+        assert_eq!(buckets["a"].saved, 1);
+
+        // Not written again until a bucket changes.
+        fs::remove_file(&path).unwrap();
+        index.save();
+        assert!(!path.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,18 +1,24 @@
 use super::*;
-use crate::{cond_stmt::CondStmt, executor::StatusType};
+use crate::{
+    cond_stmt::CondStmt,
+    crash_class::{BugClass, CrashClass, Severity},
+    executor::{ExecStatus, StatusType},
+    source_map,
+};
 use rand;
 use std::{
+    collections::{BTreeMap, HashMap},
     fs,
     io::prelude::*,
     mem,
     path::{Path, PathBuf},
     sync::{
         atomic::{AtomicUsize, Ordering},
-        Mutex,
+        Mutex, RwLock,
     },
 };
 // https://crates.io/crates/priority-queue
-use angora_common::config;
+use angora_common::{config, defs};
 use priority_queue::PriorityQueue;
 
 pub struct Depot {
@@ -20,7 +26,12 @@ pub struct Depot {
     pub num_inputs: AtomicUsize,
     pub num_hangs: AtomicUsize,
     pub num_crashes: AtomicUsize,
+    pub num_unconfirmed_crashes: AtomicUsize,
+    pub num_ooms: AtomicUsize,
     pub dirs: DepotDir,
+    // Files of the inputs in the queue, whose names tell where they come from.
+    pub(super) input_paths: RwLock<HashMap<usize, PathBuf>>,
+    crash_index: CrashIndex,
 }
 
 impl Depot {
@@ -30,51 +41,172 @@ impl Depot {
             num_inputs: AtomicUsize::new(0),
             num_hangs: AtomicUsize::new(0),
             num_crashes: AtomicUsize::new(0),
+            num_unconfirmed_crashes: AtomicUsize::new(0),
+            num_ooms: AtomicUsize::new(0),
             dirs: DepotDir::new(in_dir, out_dir),
+            input_paths: RwLock::new(HashMap::new()),
+            crash_index: CrashIndex::new(
+                out_dir.join(defs::CRASHES_DIR).join(defs::CRASH_INDEX_FILE),
+            ),
         }
     }
 
-    fn save_input(
-        status: &StatusType,
-        buf: &Vec<u8>,
-        num: &AtomicUsize,
-        cmpid: u32,
-        dir: &Path,
-    ) -> usize {
+    fn next_id(status: &ExecStatus, num: &AtomicUsize, cmpid: u32) -> usize {
         let id = num.fetch_add(1, Ordering::Relaxed);
         trace!(
-            "Find {} th new {:?} input by fuzzing cmpid 0x{:08x}.",
+            "Find {} th new {:?} input by fuzzing cmpid 0x{:08x}{}.",
             id,
             status,
-            cmpid
+            cmpid,
+            source_map::cmpid_loc(cmpid)
+                .map(|loc| format!(" at {}", loc))
+                .unwrap_or_default()
         );
-        let new_path = get_file_name(dir, id);
-        let mut f = fs::File::create(new_path.as_path()).expect("Could not save new input file.");
+        id
+    }
+
+    fn write_input(path: &Path, buf: &Vec<u8>) {
+        let mut f = fs::File::create(path).expect("Could not save new input file.");
         f.write_all(buf)
             .expect("Could not write seed buffer to file.");
         f.flush().expect("Could not flush file I/O.");
+    }
+
+    fn save_input(
+        status: &ExecStatus,
+        buf: &Vec<u8>,
+        num: &AtomicUsize,
+        cmpid: u32,
+        dir: &Path,
+    ) -> usize {
+        let id = Self::next_id(status, num, cmpid);
+        Self::write_input(&get_file_name(dir, id), buf);
         id
     }
 
-    pub fn save(&self, status: StatusType, buf: &Vec<u8>, cmpid: u32) -> usize {
-        match status {
+    /// Inputs in the queue are named after their `origin`.
+    /// Crashes go to `save_crash`, and confirmed hangs to `save_hang` instead.
+    pub fn save(
+        &self,
+        status: ExecStatus,
+        buf: &Vec<u8>,
+        origin: &Origin,
+        new_edge: bool,
+    ) -> usize {
+        match status.status {
             StatusType::Normal => {
-                Self::save_input(&status, buf, &self.num_inputs, cmpid, &self.dirs.inputs_dir)
-            },
-            StatusType::Timeout => {
-                Self::save_input(&status, buf, &self.num_hangs, cmpid, &self.dirs.hangs_dir)
+                let id = Self::next_id(&status, &self.num_inputs, origin.cmpid);
+                let path = self.dirs.inputs_dir.join(origin.file_name(id, new_edge));
+                Self::write_input(&path, buf);
+                self.input_paths.write().unwrap().insert(id, path);
+                id
             },
-            StatusType::Crash => Self::save_input(
+            StatusType::Oom => Self::save_input(
                 &status,
                 buf,
-                &self.num_crashes,
-                cmpid,
-                &self.dirs.crashes_dir,
+                &self.num_ooms,
+                origin.cmpid,
+                &self.dirs.ooms_dir,
             ),
             _ => 0,
         }
     }
 
+    /// Save the metadata of an input in the queue in `queue/.meta/`.
+    pub fn save_queue_meta(&self, meta: &QueueMeta) {
+        let path = match self.input_paths.read().unwrap().get(&meta.id) {
+            Some(path) => path.clone(),
+            None => return,
+        };
+        let dir = self.dirs.inputs_dir.join(defs::QUEUE_META_DIR);
+        let mut name = path.file_name().unwrap().to_owned();
+        name.push(".json");
+        let meta = serde_json::to_string_pretty(meta).expect("Could not serialize!");
+        if let Err(e) = fs::create_dir_all(&dir).and_then(|_| fs::write(dir.join(name), meta)) {
+            warn!("Could not save queue metadata: {:?}", e);
+        }
+    }
+
+    /// Save the crash in `crashes/<bucket>/`.
+    pub fn save_crash(&self, status: ExecStatus, buf: &Vec<u8>, cmpid: u32, bucket: &str) -> usize {
+        let dir = self.dirs.crashes_dir.join(bucket);
+        if let Err(e) = fs::create_dir_all(&dir) {
+            warn!("Could not create crash bucket {:?}: {:?}", dir, e);
+        }
+        Self::save_input(&status, buf, &self.num_crashes, cmpid, &dir)
+    }
+
+    /// Save a crash that the sanitized program does not reproduce in `crashes_unconfirmed/`.
+    pub fn save_unconfirmed_crash(&self, status: ExecStatus, buf: &Vec<u8>, cmpid: u32) -> usize {
+        Self::save_input(
+            &status,
+            buf,
+            &self.num_unconfirmed_crashes,
+            cmpid,
+            &self.dirs.unconfirmed_crashes_dir,
+        )
+    }
+
+    /// Count a crash in the index of buckets, whether it is saved or not.
+    pub fn hit_crash_bucket(&self, bucket: &str, error: &str, class: CrashClass, saved: bool) {
+        self.crash_index.hit(bucket, error, class, saved);
+    }
+
+    pub fn save_crash_index(&self) {
+        self.crash_index.save();
+    }
+
+    /// Crash buckets of each severity and of each bug class, for the stats.
+    pub fn crash_classes(&self) -> (BTreeMap<Severity, usize>, BTreeMap<BugClass, usize>) {
+        self.crash_index.count_classes()
+    }
+
+    pub fn crash_path(&self, id: usize, bucket: &str) -> PathBuf {
+        get_file_name(&self.dirs.crashes_dir.join(bucket), id)
+    }
+
+    pub fn unconfirmed_crash_path(&self, id: usize) -> PathBuf {
+        get_file_name(&self.dirs.unconfirmed_crashes_dir, id)
+    }
+
+    /// Save the sanitizer output and the bundle next to the crash input at `path`.
+    pub fn save_crash_report(&self, path: &Path, report: &str, bundle: &CrashBundle) {
+        if let Err(e) = fs::write(path.with_extension("report"), report) {
+            warn!("Could not save crash report: {:?}", e);
+        }
+        let bundle = serde_json::to_string_pretty(bundle).expect("Could not serialize!");
+        if let Err(e) = fs::write(path.with_extension("json"), bundle) {
+            warn!("Could not save crash bundle: {:?}", e);
+        }
+    }
+
+    /// Save a hang that the sanitized program confirms, with the stack that
+    /// the sanitizer printed when the hang got SIGABRT in `id:NNNNNN.report`.
+    pub fn save_hang(&self, status: ExecStatus, buf: &Vec<u8>, cmpid: u32, report: &str) -> usize {
+        let id = Self::save_input(&status, buf, &self.num_hangs, cmpid, &self.dirs.hangs_dir);
+        let path = get_file_name(&self.dirs.hangs_dir, id);
+        if let Err(e) = fs::write(path.with_extension("report"), report) {
+            warn!("Could not save hang report: {:?}", e);
+        }
+        id
+    }
+
+    /// Save the minimized hang `id` as `id:NNNNNN.min`.
+    pub fn save_hang_min(&self, id: usize, buf: &Vec<u8>) {
+        let path = get_file_name(&self.dirs.hangs_dir, id);
+        if let Err(e) = fs::write(path.with_extension("min"), buf) {
+            warn!("Could not save minimized hang: {:?}", e);
+        }
+    }
+
+    /// Save the minimized crash `id` of `bucket` as `id:NNNNNN.min`.
+    pub fn save_crash_min(&self, id: usize, bucket: &str, buf: &Vec<u8>) {
+        let path = self.crash_path(id, bucket);
+        if let Err(e) = fs::write(path.with_extension("min"), buf) {
+            warn!("Could not save minimized crash: {:?}", e);
+        }
+    }
+
     pub fn empty(&self) -> bool {
         self.num_inputs.load(Ordering::Relaxed) == 0
     }
@@ -84,7 +216,10 @@ impl Depot {
     }
 
     pub fn get_input_buf(&self, id: usize) -> Vec<u8> {
-        let path = get_file_name(&self.dirs.inputs_dir, id);
+        let path = match self.input_paths.read().unwrap().get(&id) {
+            Some(path) => path.clone(),
+            None => get_file_name(&self.dirs.inputs_dir, id),
+        };
         read_from_file(&path)
     }
 
@@ -162,4 +297,28 @@ impl Depot {
             q.change_priority(&cond, QPriority::done());
         }
     }
+
+    /// A bit for each side of a cmpid that the queue has reached, in any context.
+    pub fn cond_sides(&self) -> HashMap<u32, u32> {
+        let q = match self.queue.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => {
+                warn!("Mutex poisoned! Results may be incorrect. Continuing...");
+                poisoned.into_inner()
+            },
+        };
+        let mut sides = HashMap::new();
+        for (cond, _) in q.iter() {
+            if cond.base.is_afl() || cond.base.is_exploitable() {
+                continue;
+            }
+            let side = if cond.is_done() {
+                0b11
+            } else {
+                1_u32.checked_shl(cond.base.condition).unwrap_or(0)
+            };
+            *sides.entry(cond.base.cmpid).or_insert(0) |= side;
+        }
+        sides
+    }
 }
@@ -9,24 +9,39 @@ pub struct DepotDir {
     pub inputs_dir: PathBuf,
     pub hangs_dir: PathBuf,
     pub crashes_dir: PathBuf,
+    // Crashes of the fast program that the sanitized one disagrees with.
+    pub unconfirmed_crashes_dir: PathBuf,
+    pub ooms_dir: PathBuf,
     pub seeds_dir: PathBuf,
+    // Coverage maps saved by `GlobalBranches`, to resume from.
+    pub branches_dir: PathBuf,
 }
 
 impl DepotDir {
+    /// The directories may exist if the campaign is resumed in place.
     pub fn new(seeds_dir: PathBuf, out_dir: &Path) -> Self {
         let inputs_dir = out_dir.join(defs::INPUTS_DIR);
         let hangs_dir = out_dir.join(defs::HANGS_DIR);
         let crashes_dir = out_dir.join(defs::CRASHES_DIR);
+        let unconfirmed_crashes_dir = out_dir.join(defs::UNCONFIRMED_CRASHES_DIR);
+        let ooms_dir = out_dir.join(defs::OOMS_DIR);
+        let branches_dir = out_dir.join(defs::BRANCHES_DIR);
 
-        fs::create_dir(&crashes_dir).unwrap();
-        fs::create_dir(&hangs_dir).unwrap();
-        fs::create_dir(&inputs_dir).unwrap();
+        fs::create_dir_all(&crashes_dir).unwrap();
+        fs::create_dir_all(&unconfirmed_crashes_dir).unwrap();
+        fs::create_dir_all(&ooms_dir).unwrap();
+        fs::create_dir_all(&hangs_dir).unwrap();
+        fs::create_dir_all(&inputs_dir).unwrap();
+        fs::create_dir_all(&branches_dir).unwrap();
 
         Self {
             inputs_dir,
             hangs_dir,
             crashes_dir,
+            unconfirmed_crashes_dir,
+            ooms_dir,
             seeds_dir,
+            branches_dir,
         }
     }
 }
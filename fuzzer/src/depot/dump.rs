@@ -1,4 +1,5 @@
 use super::*;
+use crate::source_map;
 use angora_common::{config::FuzzerConfig, defs};
 use std::{fs, io::prelude::*};
 
@@ -17,7 +18,7 @@ impl Drop for Depot {
         let mut log_q = fs::File::create(dir.join(defs::COND_QUEUE_FILE)).unwrap();
         writeln!(
             log_q,
-            "cmpid, context, order, belong, op, p, condition, is_desirable, offsets, state"
+            "cmpid, context, order, belong, op, p, condition, is_desirable, offsets, state, location"
         )
         .unwrap();
         let q = self.queue.lock().unwrap();
@@ -31,7 +32,7 @@ impl Drop for Depot {
 
                 writeln!(
                     log_q,
-                    "0x{:08x}, {}, {}, {}, 0x{:x}, {}, {}, {}, {}, {:?}",
+                    "0x{:08x}, {}, {}, {}, 0x{:x}, {}, {}, {}, {}, {:?}, {}",
                     cond.base.cmpid,
                     cond.base.context,
                     cond.base.order,
@@ -41,7 +42,10 @@ impl Drop for Depot {
                     cond.base.condition,
                     cond.is_desirable,
                     offsets.join("&"),
-                    cond.state
+                    cond.state,
+                    source_map::cmpid_loc(cond.base.cmpid)
+                        .map(|loc| loc.to_string())
+                        .unwrap_or_default()
                 )
                 .unwrap();
             }
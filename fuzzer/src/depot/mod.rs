@@ -1,9 +1,20 @@
+mod checkpoint;
+mod crash_bundle;
+mod crash_index;
 mod depot;
 mod depot_dir;
 mod dump;
 mod file;
+mod provenance;
 mod qpriority;
 mod sync;
 
-pub use self::{depot::Depot, file::*, sync::*};
-use self::{depot_dir::DepotDir, qpriority::QPriority};
+pub use self::{
+    checkpoint::DepotCheckpoint,
+    crash_bundle::{CrashBundle, Reproducibility},
+    depot::Depot,
+    file::*,
+    provenance::{Origin, QueueMeta},
+    sync::*,
+};
+use self::{crash_index::CrashIndex, depot_dir::DepotDir, qpriority::QPriority};
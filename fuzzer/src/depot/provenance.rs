@@ -0,0 +1,148 @@
+use crate::{cond_stmt::CondStmt, fuzz_type::FuzzType};
+use serde_derive::Serialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where a new input in the queue comes from, in its file name, e.g.
+/// `id:000123,src:000045,op:gd,cmp:0x1a3f2,+cov`.
+#[derive(Debug, Clone, Default)]
+pub struct Origin {
+    // The input that was mutated, None for the seeds and inputs synced from AFL.
+    pub src: Option<usize>,
+    pub op: &'static str,
+    pub cmpid: u32,
+}
+
+impl Origin {
+    pub fn from_cond(cond: &CondStmt) -> Self {
+        let op = match cond.get_fuzz_type() {
+            FuzzType::ExploreFuzz if cond.state.is_one_byte() => "onebyte",
+            FuzzType::ExploreFuzz if cond.state.is_det() => "det",
+            FuzzType::ExploreFuzz => "gd",
+            FuzzType::ExploitIntFuzz => "expint",
+            FuzzType::ExploitMemFuzz => "expmem",
+            FuzzType::ExploitRandFuzz => "exprand",
+            FuzzType::CmpFnFuzz => "cmpfn",
+            FuzzType::LenFuzz => "len",
+            FuzzType::AFLFuzz => "afl",
+            FuzzType::OtherFuzz => "other",
+        };
+        Self {
+            src: Some(cond.base.belong as usize),
+            op,
+            cmpid: cond.base.cmpid,
+        }
+    }
+
+    pub fn sync() -> Self {
+        Self {
+            src: None,
+            op: "sync",
+            cmpid: 0,
+        }
+    }
+
+    /// The file name of input `id`, with `+cov` if it covers new edges,
+    /// not only new hit count buckets of known ones.
+    pub fn file_name(&self, id: usize, new_edge: bool) -> String {
+        let mut name = format!("id:{:06}", id);
+        if let Some(src) = self.src {
+            name += &format!(",src:{:06}", src);
+        }
+        name += &format!(",op:{}", self.op);
+        if self.cmpid != 0 {
+            name += &format!(",cmp:0x{:x}", self.cmpid);
+        }
+        if new_edge {
+            name += ",+cov";
+        }
+        name
+    }
+}
+
+/// A condition tracked from an input, which later rounds of fuzzing try to solve.
+#[derive(Debug, Serialize)]
+pub struct TrackedCond {
+    pub cmpid: u32,
+    pub context: u32,
+    pub order: u32,
+    pub op: u32,
+    // Bytes of the input that it depends on.
+    pub offsets: u32,
+}
+
+impl TrackedCond {
+    pub fn new(cond: &CondStmt) -> Self {
+        Self {
+            cmpid: cond.base.cmpid,
+            context: cond.base.context,
+            order: cond.base.order,
+            op: cond.base.op,
+            offsets: cond.offsets.iter().map(|off| off.end - off.begin).sum(),
+        }
+    }
+}
+
+/// Saved as `queue/.meta/<file name>.json` with `--queue_metadata`.
+#[derive(Debug, Serialize)]
+pub struct QueueMeta {
+    pub id: usize,
+    pub src: Option<usize>,
+    pub op: &'static str,
+    pub cmpid: u32,
+    // The fuzzing thread that found it.
+    pub thread: usize,
+    pub new_edge: bool,
+    // Average of the calibration runs, in us.
+    pub exec_time: u32,
+    pub edge_num: usize,
+    // Seconds since the epoch.
+    pub time: u64,
+    pub conds: Vec<TrackedCond>,
+}
+
+impl QueueMeta {
+    pub fn new(
+        id: usize,
+        origin: &Origin,
+        thread: usize,
+        new_edge: bool,
+        exec_time: u32,
+        edge_num: usize,
+        conds: &[CondStmt],
+    ) -> Self {
+        Self {
+            id,
+            src: origin.src,
+            op: origin.op,
+            cmpid: origin.cmpid,
+            thread,
+            new_edge,
+            exec_time,
+            edge_num,
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            conds: conds.iter().map(TrackedCond::new).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_name() {
+        let origin = Origin {
+            src: Some(45),
+            op: "gd",
+            cmpid: 0x1a3f2,
+        };
+        assert_eq!(
+            origin.file_name(123, true),
+            "id:000123,src:000045,op:gd,cmp:0x1a3f2,+cov"
+        );
+        assert_eq!(Origin::sync().file_name(7, false), "id:000007,op:sync");
+    }
+}
@@ -11,8 +11,8 @@ use std::{
     },
 };
 
-pub fn sync_depot(executor: &mut Executor, running: Arc<AtomicBool>, dir: &Path) {
-    executor.local_stats.clear();
+pub fn sync_depot(executor: &mut dyn Executor, running: Arc<AtomicBool>, dir: &Path) {
+    executor.local_stats().clear();
     let seed_dir = dir.read_dir().expect("read_dir call failed");
     for entry in seed_dir {
         if let Ok(entry) = entry {
@@ -32,19 +32,22 @@ pub fn sync_depot(executor: &mut Executor, running: Arc<AtomicBool>, dir: &Path)
             }
         }
     }
-    info!("sync {} file from seeds.", executor.local_stats.num_inputs);
+    info!(
+        "sync {} file from seeds.",
+        executor.local_stats().num_inputs
+    );
     executor.update_log();
 }
 
 // Now we are in a sub-dir of AFL's output dir
 pub fn sync_afl(
-    executor: &mut Executor,
+    executor: &mut dyn Executor,
     running: Arc<AtomicBool>,
     sync_dir: &Path,
     sync_ids: &mut HashMap<String, usize>,
 ) {
-    executor.rebind_forksrv();
-    executor.local_stats.clear();
+    executor.restart();
+    executor.local_stats().clear();
 
     if let Ok(entries) = sync_dir.read_dir() {
         for entry in entries {
@@ -68,7 +71,7 @@ pub fn sync_afl(
         }
     }
 
-    let n: usize = executor.local_stats.num_inputs.into();
+    let n: usize = executor.local_stats().num_inputs.into();
     info!("sync {} file from AFL.", n);
 
     executor.update_log();
@@ -88,7 +91,7 @@ fn get_afl_id(f: &fs::DirEntry) -> Option<usize> {
 }
 
 fn sync_one_afl_dir(
-    executor: &mut Executor,
+    executor: &mut dyn Executor,
     running: Arc<AtomicBool>,
     sync_dir: &Path,
     sync_name: &str,
@@ -124,3 +127,118 @@ fn sync_one_afl_dir(
 
     sync_ids.insert(sync_name.to_string(), max_id + 1);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        branches::GlobalBranches,
+        command::CommandOpt,
+        cond_stmt::CondStmt,
+        executor::StatusType,
+        stats::{ChartStats, LocalStats},
+    };
+    use std::{env, process, sync::RwLock};
+
+    // Records the inputs that it is given.
+    #[derive(Default)]
+    struct MockExecutor {
+        synced: Vec<Vec<u8>>,
+        local_stats: LocalStats,
+    }
+
+    impl Executor for MockExecutor {
+        fn new(
+            _cmd: CommandOpt,
+            _global_branches: Arc<GlobalBranches>,
+            _depot: Arc<Depot>,
+            _global_stats: Arc<RwLock<ChartStats>>,
+            _running: Arc<AtomicBool>,
+        ) -> Self {
+            Self::default()
+        }
+
+        fn run(&mut self, _buf: &Vec<u8>, _cond: &mut CondStmt) -> StatusType {
+            StatusType::Normal
+        }
+
+        fn run_with_cond(&mut self, _buf: &Vec<u8>, _cond: &mut CondStmt) -> (StatusType, i128) {
+            (StatusType::Normal, 0)
+        }
+
+        fn run_sync(&mut self, buf: &Vec<u8>) {
+            self.synced.push(buf.clone());
+        }
+
+        fn update_log(&mut self) {}
+
+        fn random_input_buf(&self) -> Vec<u8> {
+            vec![]
+        }
+
+        fn restart(&mut self) {}
+
+        fn set_time_limit(&mut self, _time_limit: u64) {}
+
+        fn time_limit(&self) -> u64 {
+            config::TIME_LIMIT
+        }
+
+        fn max_exec_time(&self) -> u32 {
+            0
+        }
+
+        fn has_new_path(&self) -> bool {
+            false
+        }
+
+        fn last_f(&self) -> i128 {
+            0
+        }
+
+        fn local_stats(&mut self) -> &mut LocalStats {
+            &mut self.local_stats
+        }
+    }
+
+    #[test]
+    fn test_sync_depot() {
+        let dir = env::temp_dir().join(format!("angora_sync_depot_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a"), b"a").unwrap();
+        fs::write(dir.join("b"), b"b").unwrap();
+        let mut executor = MockExecutor::default();
+        sync_depot(&mut executor, Arc::new(AtomicBool::new(true)), &dir);
+        executor.synced.sort();
+        assert_eq!(executor.synced, vec![b"a".to_vec(), b"b".to_vec()]);
+
+        // Stopped before the first one.
+        let mut executor = MockExecutor::default();
+        sync_depot(&mut executor, Arc::new(AtomicBool::new(false)), &dir);
+        assert!(executor.synced.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sync_afl() {
+        let dir = env::temp_dir().join(format!("angora_sync_afl_{}", process::id()));
+        let queue = dir.join("queue");
+        fs::create_dir_all(&queue).unwrap();
+        fs::write(queue.join("id:000000,orig:seed"), b"0").unwrap();
+        fs::write(queue.join("id:000001,src:000000"), b"1").unwrap();
+        let running = Arc::new(AtomicBool::new(true));
+        let mut sync_ids = HashMap::new();
+        let mut executor = MockExecutor::default();
+        sync_afl(&mut executor, running.clone(), &dir, &mut sync_ids);
+        assert_eq!(executor.synced.len(), 2);
+        assert_eq!(sync_ids["queue"], 2);
+
+        // Only the new inputs of the other fuzzer are run again.
+        fs::write(queue.join("id:000002,src:000001"), b"2").unwrap();
+        let mut executor = MockExecutor::default();
+        sync_afl(&mut executor, running, &dir, &mut sync_ids);
+        assert_eq!(executor.synced, vec![b"2".to_vec()]);
+        assert_eq!(sync_ids["queue"], 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,145 @@
+// cgroup v2 limits for the programs, instead of RLIMIT_AS.
+use std::{
+    ffi::{CStr, CString},
+    fs,
+    io::{self, ErrorKind},
+    os::unix::ffi::OsStrExt,
+    path::PathBuf,
+    process,
+};
+
+static MOUNTINFO_FILE: &str = "/proc/self/mountinfo";
+static SELF_CGROUP_FILE: &str = "/proc/self/cgroup";
+// The fuzzer itself moves here, since only leaf cgroups can have processes.
+static FUZZER_CGROUP: &str = "angora_fuzzer";
+static CONTROLLERS: &str = "+memory +pids";
+
+fn cgroup2_mount() -> Option<PathBuf> {
+    let mountinfo = fs::read_to_string(MOUNTINFO_FILE).ok()?;
+    mountinfo.lines().find_map(|line| {
+        // ... mount_point ... - fstype source options
+        let (fields, fs_fields) = line.split_at(line.find(" - ")?);
+        if fs_fields.split_whitespace().nth(1)? == "cgroup2" {
+            fields.split_whitespace().nth(4).map(PathBuf::from)
+        } else {
+            None
+        }
+    })
+}
+
+fn self_cgroup() -> Option<String> {
+    let cgroups = fs::read_to_string(SELF_CGROUP_FILE).ok()?;
+    cgroups
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .map(|path| path.trim_start_matches('/').to_owned())
+}
+
+/// Prepare the cgroup of the fuzzer to hold a cgroup for each executor,
+/// and return its path. None if cgroup v2 is not available or writable.
+pub fn init() -> Option<PathBuf> {
+    let base = cgroup2_mount()?.join(self_cgroup()?);
+    let available = fs::read_to_string(base.join("cgroup.controllers")).ok()?;
+    if !(available.contains("memory") && available.contains("pids")) {
+        return None;
+    }
+    let enabled = fs::read_to_string(base.join("cgroup.subtree_control")).ok()?;
+    if !(enabled.contains("memory") && enabled.contains("pids")) {
+        let leaf = base.join(FUZZER_CGROUP);
+        if let Err(e) = fs::create_dir(&leaf) {
+            if e.kind() != ErrorKind::AlreadyExists {
+                return None;
+            }
+        }
+        let moved = fs::write(leaf.join("cgroup.procs"), process::id().to_string())
+            .and_then(|_| fs::write(base.join("cgroup.subtree_control"), CONTROLLERS));
+        if let Err(e) = moved {
+            warn!("Could not enable cgroup controllers in {:?}: {:?}", base, e);
+            return None;
+        }
+    }
+    Some(base)
+}
+
+/// A cgroup with `memory.max` and `pids.max` that the programs of one executor join.
+pub struct Cgroup {
+    path: PathBuf,
+    procs: CString,
+    oom_kills: u64,
+}
+
+impl Cgroup {
+    /// `mem_limit` in MB.
+    pub fn new(path: PathBuf, mem_limit: u64, pids_max: u64) -> io::Result<Self> {
+        if let Err(e) = fs::create_dir(&path) {
+            if e.kind() != ErrorKind::AlreadyExists {
+                return Err(e);
+            }
+        }
+        fs::write(path.join("memory.max"), (mem_limit << 20).to_string())?;
+        // Swapping would only turn OOMs into timeouts.
+        let _ = fs::write(path.join("memory.swap.max"), "0");
+        fs::write(path.join("pids.max"), pids_max.to_string())?;
+        let procs = CString::new(path.join("cgroup.procs").as_os_str().as_bytes()).unwrap();
+        let mut cgroup = Self {
+            path,
+            procs,
+            oom_kills: 0,
+        };
+        cgroup.oom_kills = cgroup.read_oom_kills();
+        Ok(cgroup)
+    }
+
+    /// Where a process writes "0" to join the cgroup.
+    pub fn procs_path(&self) -> &CStr {
+        &self.procs
+    }
+
+    fn read_oom_kills(&self) -> u64 {
+        let events = fs::read_to_string(self.path.join("memory.events")).unwrap_or_default();
+        events
+            .lines()
+            .find_map(|line| line.strip_prefix("oom_kill "))
+            .and_then(|n| n.trim().parse().ok())
+            .unwrap_or(0)
+    }
+
+    /// Whether the kernel killed a process of the cgroup for running out of memory
+    /// since the last call.
+    pub fn has_new_oom_kill(&mut self) -> bool {
+        let oom_kills = self.read_oom_kills();
+        let has_new = oom_kills > self.oom_kills;
+        self.oom_kills = oom_kills;
+        has_new
+    }
+}
+
+impl Drop for Cgroup {
+    fn drop(&mut self) {
+        // Fails if the fork server has not exited yet, which leaves an empty cgroup behind.
+        if fs::remove_dir(&self.path).is_err() {
+            info!("Could not remove cgroup {:?}", self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_oom_kills() {
+        let path = env::temp_dir().join(format!("angora_cgroup_test_{}", process::id()));
+        let mut cgroup = Cgroup::new(path.clone(), 200, 16).unwrap();
+        assert_eq!(
+            fs::read_to_string(path.join("memory.max")).unwrap(),
+            "209715200"
+        );
+        assert!(!cgroup.has_new_oom_kill());
+        fs::write(path.join("memory.events"), "oom 1\noom_kill 1\n").unwrap();
+        assert!(cgroup.has_new_oom_kill());
+        assert!(!cgroup.has_new_oom_kill());
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -0,0 +1,1187 @@
+use super::{cgroup::Cgroup, limit::SetLimit, *};
+use crate::{
+    branches, command,
+    cond_stmt::{self, NextState},
+    crash_class::CrashClass,
+    depot, minimize, stats, track,
+};
+use angora_common::{
+    config::{self, FuzzerConfig},
+    debug_cmpid, defs,
+};
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    process::{self, Command, Stdio},
+    sync::{
+        atomic::{compiler_fence, AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    time,
+};
+use wait_timeout::ChildExt;
+
+/// Runs the fast program in `S`, and the track and sanitized ones under their fork servers.
+pub struct ForkExecutor<S: FastServer = Forksrv> {
+    pub cmd: command::CommandOpt,
+    pub branches: branches::Branches,
+    pub t_conds: cond_stmt::ShmConds,
+    envs: HashMap<String, String>,
+    forksrv: Option<S>,
+    track_forksrv: Option<Forksrv>,
+    san_forksrv: Option<Forksrv>,
+    // The memory and time limits that the san forksrv was started with.
+    san_forksrv_limits: (u64, u64),
+    depot: Arc<depot::Depot>,
+    fd: PipeFd,
+    cgroup: Option<Cgroup>,
+    shm_input: Option<ShmInput>,
+    net_input: Option<NetInput>,
+    tmout_cnt: usize,
+    invariable_cnt: usize,
+    max_exec_time: u32,
+    // Of the input being run, to name it if it is saved in the queue.
+    origin: depot::Origin,
+    pub last_f: i128,
+    pub has_new_path: bool,
+    pub global_stats: Arc<RwLock<stats::ChartStats>>,
+    pub local_stats: stats::LocalStats,
+    // Cleared to stop the fuzzer, e.g. by the executor itself if the maps cannot hold its branches.
+    running: Arc<AtomicBool>,
+}
+
+impl<S: FastServer> ForkExecutor<S> {
+    pub fn rebind_forksrv(&mut self) {
+        info!("Rebinding forkserver");
+        {
+            // delete the old forksrv
+            self.forksrv = None;
+        }
+        let (rlimit, cgroup) = self.limits(self.cmd.mem_limit);
+        let fs = S::start(
+            &self.cmd.forksrv_socket_path,
+            &self.cmd.main,
+            &self.envs,
+            self.fd.as_raw_fd(),
+            self.cmd.is_stdin,
+            self.cmd.uses_asan,
+            self.cmd.time_limit,
+            rlimit,
+            cgroup,
+        );
+        self.forksrv = Some(fs);
+        self.resize_branches();
+    }
+
+    // The maps are shared by all the executors, so a program whose branch table
+    // outgrows them cannot be fuzzed any further.
+    fn resize_branches(&mut self) {
+        if let Err(e) = self.branches.resize() {
+            error!("{} Stop fuzzing.", e);
+            self.running.store(false, Ordering::Relaxed);
+        }
+    }
+
+    // The track forksrv is started on the first tracking run.
+    fn rebind_track_forksrv(&mut self) {
+        info!("Rebinding track forkserver");
+        {
+            // delete the old forksrv
+            self.track_forksrv = None;
+        }
+        let fs = forksrv::Forksrv::new(
+            &self.cmd.track_forksrv_socket_path,
+            &self.cmd.track,
+            &self.envs,
+            self.fd.as_raw_fd(),
+            self.cmd.is_stdin,
+            false,
+            self.cmd.track_time_limit(),
+            config::MEM_LIMIT_TRACK,
+            None,
+        );
+        self.track_forksrv = Some(fs);
+    }
+
+    // Like the track forksrv, it is started by the first run of the sanitized program.
+    fn rebind_san_forksrv(&mut self, mem_limit: u64, time_limit: u64) {
+        info!("Rebinding sanitizer forkserver");
+        {
+            // delete the old forksrv
+            self.san_forksrv = None;
+        }
+        let fs = forksrv::Forksrv::new(
+            &self.cmd.san_forksrv_socket_path,
+            &self.cmd.san,
+            &self.envs,
+            self.fd.as_raw_fd(),
+            self.cmd.is_stdin,
+            true,
+            time_limit,
+            mem_limit,
+            None,
+        );
+        self.san_forksrv = Some(fs);
+        self.san_forksrv_limits = (mem_limit, time_limit);
+    }
+
+    // The cgroup, if any, replaces RLIMIT_AS for limited runs.
+    fn limits(&self, mem_limit: u64) -> (u64, Option<&Cgroup>) {
+        match self.cgroup {
+            Some(ref cgroup) if mem_limit != 0 => (0, Some(cgroup)),
+            _ => (mem_limit, None),
+        }
+    }
+
+    // FIXME: The location id may be inconsistent between track and fast programs.
+    fn check_consistent(&self, output: i128, cond: &mut cond_stmt::CondStmt) {
+        if output == defs::UNREACHABLE
+            && cond.is_first_time()
+            && self.local_stats.num_exec == 1.into()
+            && cond.state.is_initial()
+        {
+            cond.is_consistent = false;
+            warn!("inconsistent : {:?}", cond);
+        }
+    }
+
+    fn check_invariable(&mut self, output: i128, cond: &mut cond_stmt::CondStmt) -> bool {
+        let mut skip = false;
+        if output == self.last_f {
+            self.invariable_cnt += 1;
+            if self.invariable_cnt >= config::MAX_INVARIABLE_NUM {
+                debug_cmpid!(
+                    self.t_conds.cond.cmpid,
+                    "output is invariable! f: {}",
+                    output
+                );
+                if cond.is_desirable {
+                    cond.is_desirable = false;
+                }
+                // deterministic will not skip
+                if !cond.state.is_det() && !cond.state.is_one_byte() {
+                    skip = true;
+                }
+            }
+        } else {
+            self.invariable_cnt = 0;
+        }
+        self.last_f = output;
+        skip
+    }
+
+    fn check_explored(
+        &self,
+        cond: &mut cond_stmt::CondStmt,
+        _status: StatusType,
+        output: i128,
+        explored: &mut bool,
+    ) -> bool {
+        let mut skip = false;
+        // If crash or timeout, constraints after the point won't be tracked.
+        if cond.is_solved(output) && !cond.is_done()
+        //&& status == StatusType::Normal
+        {
+            debug_cmpid!(self.t_conds.cond.cmpid, "Explored this condition!(output = {}, is solved but not done, marked as done now.)", output);
+            skip = true;
+            *explored = true;
+            cond.mark_as_done();
+        }
+        skip
+    }
+
+    /// Rerun a crash with the sanitized program, which has no memory limit,
+    /// to tell OOMs from genuine crashes.
+    /// Returns the sanitizer report, and whether the crash is reproduced.
+    fn classify_crash(&mut self, buf: &Vec<u8>, status: &mut ExecStatus) -> (String, bool) {
+        let (san_status, san_stderr) =
+            self.run_with_san(buf, config::MEM_LIMIT_TRACK, self.cmd.san_time_limit());
+        let is_limited = self.cmd.mem_limit != 0;
+        let reproduced = confirm_crash(status, &san_status, || {
+            is_limited && self.crash_needs_mem_limit(buf)
+        });
+        if reproduced {
+            info!("Crash! {:?} stderr: {}", status, san_stderr);
+        } else if status.status == StatusType::Oom {
+            info!("OOM! {:?}", status);
+        } else {
+            warn!("Mismatched return status between sanitized program and instrumented program! asan status: {:?} fast status: {:?}, asan stderr: \"{}\"", san_status, status, san_stderr);
+        }
+        (san_stderr, reproduced)
+    }
+
+    pub fn run_with_san(
+        &mut self,
+        buf: &Vec<u8>,
+        mem_limit: u64,
+        time_limit: u64,
+    ) -> (ExecStatus, String) {
+        self.sync_test_file(buf);
+        if self.cmd.is_stdin {
+            self.fd.rewind();
+        }
+        if self.cmd.san_forksrv {
+            return self.run_with_san_forksrv(mem_limit, time_limit);
+        }
+
+        let orig_san_type = self.cmd.uses_asan;
+        self.cmd.uses_asan = true;
+
+        compiler_fence(Ordering::SeqCst);
+        let mut cmd = Command::new(&self.cmd.san.0);
+        let cmd = cmd
+            .args(&self.cmd.san.1)
+            .stdin(Stdio::null())
+            .env_clear()
+            .envs(&self.envs)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .mem_limit(mem_limit)
+            .setsid()
+            .pipe_stdin(self.fd.as_raw_fd(), self.cmd.is_stdin);
+        let _net_lock = self.net_input.as_ref().map(|n| n.lock());
+        let mut child = cmd.spawn().expect("Could not run target");
+        let _net_conn = self
+            .net_input
+            .as_ref()
+            .and_then(|n| n.send(child.id() as i32, time_limit));
+
+        let timeout = time::Duration::from_millis(time_limit);
+        compiler_fence(Ordering::SeqCst);
+
+        let mut ret = match child.wait_timeout(timeout).unwrap() {
+            Some(san_status) => self.get_exec_status(&san_status),
+            None => {
+                // Timeout
+                // child hasn't exited yet
+                child.kill().expect("Could not send kill signal to child.");
+                child.wait().expect("Error during waiting for child.");
+                ExecStatus::new(StatusType::Timeout)
+            }
+        };
+        self.cmd.uses_asan = orig_san_type;
+        let mut stderr = vec![];
+        if let Some(mut f) = child.stderr {
+            use std::io::Read;
+            let _ = f.read_to_end(&mut stderr);
+        }
+        let stderr = String::from_utf8(stderr).unwrap_or("".to_string());
+        ret.update_from_san_report(&stderr);
+        (ret, stderr)
+    }
+
+    // The forksrv child writes its stderr to `san_stderr_path`. It is restarted
+    // if the limits are not the ones it was started with.
+    fn run_with_san_forksrv(&mut self, mem_limit: u64, time_limit: u64) -> (ExecStatus, String) {
+        if self.san_forksrv.is_none() || self.san_forksrv_limits != (mem_limit, time_limit) {
+            self.rebind_san_forksrv(mem_limit, time_limit);
+        }
+
+        let net_input = &self.net_input;
+        let _net_lock = net_input.as_ref().map(|n| n.lock());
+        compiler_fence(Ordering::SeqCst);
+        let mut ret = self
+            .san_forksrv
+            .as_mut()
+            .unwrap()
+            .run_with(|pid| net_input.as_ref().and_then(|n| n.send(pid, time_limit)));
+        compiler_fence(Ordering::SeqCst);
+
+        if ret.status == StatusType::Error {
+            self.rebind_san_forksrv(mem_limit, time_limit);
+            return (ret, String::new());
+        }
+        let stderr = fs::read(&self.cmd.san_stderr_path).unwrap_or_default();
+        let stderr = String::from_utf8_lossy(&stderr).into_owned();
+        ret.update_from_san_report(&stderr);
+        (ret, stderr)
+    }
+
+    /// Whether the fast program stops crashing on `buf` without the memory limit.
+    /// The trace is then restored from a run with the limit, for `has_new`.
+    fn crash_needs_mem_limit(&mut self, buf: &Vec<u8>) -> bool {
+        let unmem_status = self.rerun_target(buf, config::MEM_LIMIT_TRACK);
+        self.rerun_target(buf, self.cmd.mem_limit);
+        unmem_status.status == StatusType::Normal
+    }
+
+    fn rerun_target(&mut self, buf: &Vec<u8>, mem_limit: u64) -> ExecStatus {
+        self.sync_test_file(buf);
+        self.branches.clear_trace();
+        if self.cmd.is_stdin {
+            self.fd.rewind();
+        }
+        compiler_fence(Ordering::SeqCst);
+        let status = self.run_target(&self.cmd.main, mem_limit, self.cmd.time_limit);
+        compiler_fence(Ordering::SeqCst);
+        status
+    }
+
+    fn try_unlimited_memory(&mut self, buf: &Vec<u8>, cmpid: u32) -> bool {
+        let mut skip = false;
+        let unmem_status = self.rerun_target(buf, config::MEM_LIMIT_TRACK);
+
+        // find difference
+        if unmem_status.status != StatusType::Normal {
+            skip = true;
+            warn!(
+                "Behavior changes if we unlimit memory!! status={:?}",
+                unmem_status
+            );
+            // A crash or hang is confirmed, bucketed and saved as any other.
+            self.do_if_has_new(buf, unmem_status, false, cmpid);
+        }
+        skip
+    }
+
+    /// Traverse coverage bitmap to check if any new path found
+    /// Invoked by `run`, `run_with_cond`, `run_sync`
+    fn do_if_has_new(
+        &mut self,
+        buf: &Vec<u8>,
+        mut exec_status: ExecStatus,
+        _explored: bool,
+        cmpid: u32,
+    ) {
+        if exec_status.status == StatusType::Timeout {
+            self.do_if_new_hang(buf, exec_status, cmpid);
+            return;
+        }
+        let (san_stderr, reproduced) = if exec_status.status == StatusType::Crash {
+            self.classify_crash(buf, &mut exec_status)
+        } else {
+            (String::new(), false)
+        };
+        let status = exec_status.status;
+
+        // new edge: one byte in bitmap
+        let (mut has_new_path, mut has_new_edge, edge_num) = self.branches.has_new(status);
+        // If a path has two crashing points, there would be no path difference.
+        // We distinguish them using crashing output from the sanitizers,
+        // or the signal and the path if there is none.
+        let mut bucket = String::new();
+        let mut new_bucket = false;
+        if status == StatusType::Crash && !reproduced {
+            // Kept aside instead of bucketed, since flaky crashes are still bugs.
+            if has_new_path {
+                self.has_new_path = true;
+                let id = self.depot.save_unconfirmed_crash(exec_status, buf, cmpid);
+                self.save_crash_bundle(id, &exec_status, String::new(), cmpid, &san_stderr, None);
+            }
+            return;
+        }
+        if status == StatusType::Crash {
+            let crash_info =
+                branches::CrashInfo::from_output_string(&san_stderr).unwrap_or_else(|| {
+                    branches::CrashInfo::from_status(&exec_status, self.branches.path_hash())
+                });
+            bucket = crash_info.bucket_hash();
+            let error = crash_info.error().to_owned();
+            let tup = self.branches.dedup_crash(crash_info);
+            new_bucket = tup.0;
+            has_new_path |= tup.0;
+            has_new_edge |= tup.1;
+            let class = CrashClass::classify(&san_stderr, &exec_status);
+            self.depot
+                .hit_crash_bucket(&bucket, &error, class, has_new_path);
+        }
+        debug_cmpid!(
+            self.t_conds.cond.cmpid,
+            "has new path/edge: {}/{}, # edge: {}",
+            has_new_path,
+            has_new_edge,
+            edge_num
+        );
+
+        if has_new_path {
+            self.has_new_path = true;
+            debug_cmpid!(self.t_conds.cond.cmpid, "Has new path!");
+            self.local_stats.find_new(&status);
+            let id = if status == StatusType::Crash {
+                self.depot.save_crash(exec_status, buf, cmpid, &bucket)
+            } else {
+                self.depot
+                    .save(exec_status, &buf, &self.origin, has_new_edge)
+            };
+
+            if status == StatusType::Crash {
+                // Only checked once per bucket, since the reruns take the time of many executions.
+                let reproducibility = if new_bucket {
+                    Some(self.check_reproducibility(buf))
+                } else {
+                    None
+                };
+                self.save_crash_bundle(
+                    id,
+                    &exec_status,
+                    bucket.clone(),
+                    cmpid,
+                    &san_stderr,
+                    reproducibility,
+                );
+                if new_bucket && FuzzerConfig::get().minimize_crashes() {
+                    self.minimize_crash(buf, id, &bucket);
+                }
+            }
+
+            if status == StatusType::Normal {
+                self.local_stats.avg_edge_num.update(edge_num as f32);
+                let speed = self.count_time();
+                if speed != defs::SLOW_SPEED && speed > self.max_exec_time {
+                    self.max_exec_time = speed;
+                }
+                let speed_ratio = self.local_stats.avg_exec_time.get_ratio(speed as f32);
+                self.local_stats.avg_exec_time.update(speed as f32);
+
+                // Avoid track slow ones
+                let cond_stmts = if (!has_new_edge && speed_ratio > 10 && id > 10)
+                    || (speed_ratio > 25 && id > 10)
+                {
+                    warn!(
+                        "Skip tracking id {}, speed: {}, speed_ratio: {}, has_new_edge: {}",
+                        id, speed, speed_ratio, has_new_edge
+                    );
+                    vec![]
+                } else if self.try_unlimited_memory(buf, cmpid) {
+                    vec![]
+                } else {
+                    self.track(id, buf, speed)
+                };
+                if FuzzerConfig::get().queue_metadata() {
+                    let meta = depot::QueueMeta::new(
+                        id,
+                        &self.origin,
+                        self.cmd.id,
+                        has_new_edge,
+                        speed,
+                        edge_num,
+                        &cond_stmts,
+                    );
+                    self.depot.save_queue_meta(&meta);
+                }
+                if cond_stmts.len() > 0 {
+                    self.depot.add_entries(cond_stmts);
+                    if FuzzerConfig::get().enable_afl() {
+                        self.depot
+                            .add_entries(vec![cond_stmt::CondStmt::get_afl_cond(
+                                id, speed, edge_num,
+                            )]);
+                    }
+                }
+            }
+        }
+    }
+
+    /// A timeout on a new path is a hang only if the sanitized program still runs
+    /// with a longer time limit. Hangs are then told apart by where they are stuck,
+    /// and each new one is saved along with a minimized input.
+    fn do_if_new_hang(&mut self, buf: &Vec<u8>, exec_status: ExecStatus, cmpid: u32) {
+        // The path is consumed even if the input turns out to be just slow,
+        // so that slow inputs are not rerun over and over.
+        if !self.branches.has_new(StatusType::Timeout).0 {
+            return;
+        }
+        let path_hash = self.branches.path_hash();
+        let (hang_status, report) = self.run_hang(buf);
+        if hang_status.status != StatusType::Timeout {
+            info!("Slow but not hanging: {:?}", hang_status);
+            return;
+        }
+        let hang_info = branches::CrashInfo::from_hang_report(&report, path_hash);
+        if !self.branches.dedup_hang(hang_info) {
+            return;
+        }
+
+        self.has_new_path = true;
+        self.local_stats.find_new(&StatusType::Timeout);
+        let id = self.depot.save_hang(exec_status, buf, cmpid, &report);
+        let min_buf = minimize::minimize(buf, config::HANG_MIN_EXECS, |candidate| {
+            match self.run_inner(candidate).status {
+                StatusType::Timeout => true,
+                StatusType::Error => {
+                    self.rebind_forksrv();
+                    false
+                },
+                _ => false,
+            }
+        });
+        if min_buf.len() < buf.len() {
+            info!(
+                "Minimize hang {} from {} to {} bytes",
+                id,
+                buf.len(),
+                min_buf.len()
+            );
+            self.depot.save_hang_min(id, &min_buf);
+        }
+    }
+
+    /// Rerun a timeout in the sanitized program with `hang_time_limit`, and if it
+    /// still runs, send SIGABRT so that the sanitizer prints the stack.
+    /// Returns the status and the stderr of the program.
+    fn run_hang(&mut self, buf: &Vec<u8>) -> (ExecStatus, String) {
+        self.sync_test_file(buf);
+        if self.cmd.is_stdin {
+            self.fd.rewind();
+        }
+        let time_limit = self.cmd.hang_time_limit();
+        let mut cmd = Command::new(&self.cmd.san.0);
+        let cmd = cmd
+            .args(&self.cmd.san.1)
+            .stdin(Stdio::null())
+            .env_clear()
+            .envs(&self.envs)
+            .env(
+                defs::ASAN_OPTIONS_VAR,
+                command::hang_san_options(defs::ASAN_OPTIONS_CONTENT),
+            )
+            .env(
+                defs::MSAN_OPTIONS_VAR,
+                command::hang_san_options(defs::MSAN_OPTIONS_CONTENT),
+            )
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .mem_limit(config::MEM_LIMIT_TRACK)
+            .setsid()
+            .pipe_stdin(self.fd.as_raw_fd(), self.cmd.is_stdin);
+        let _net_lock = self.net_input.as_ref().map(|n| n.lock());
+        let mut child = cmd.spawn().expect("Could not run target");
+        let _net_conn = self
+            .net_input
+            .as_ref()
+            .and_then(|n| n.send(child.id() as i32, time_limit));
+
+        let timeout = time::Duration::from_millis(time_limit);
+        let ret = match child.wait_timeout(timeout).unwrap() {
+            Some(status) => ExecStatus::from_exit_status(&status, true),
+            None => {
+                unsafe {
+                    libc::kill(child.id() as i32, libc::SIGABRT);
+                }
+                let wait = time::Duration::from_millis(config::HANG_REPORT_WAIT);
+                if child.wait_timeout(wait).unwrap().is_none() {
+                    child.kill().expect("Could not send kill signal to child.");
+                    child.wait().expect("Error during waiting for child.");
+                }
+                ExecStatus::new(StatusType::Timeout)
+            },
+        };
+        let mut stderr = vec![];
+        if let Some(mut f) = child.stderr {
+            use std::io::Read;
+            let _ = f.read_to_end(&mut stderr);
+        }
+        (ret, String::from_utf8_lossy(&stderr).into_owned())
+    }
+
+    /// Save the report and the bundle of crash `id` in `bucket`, or of the
+    /// unconfirmed crash `id` if `bucket` is empty.
+    fn save_crash_bundle(
+        &mut self,
+        id: usize,
+        exec_status: &ExecStatus,
+        bucket: String,
+        cmpid: u32,
+        san_stderr: &str,
+        reproducibility: Option<depot::Reproducibility>,
+    ) {
+        let path = if bucket.is_empty() {
+            self.depot.unconfirmed_crash_path(id)
+        } else {
+            self.depot.crash_path(id, &bucket)
+        };
+        let command = self.cmd.san_cmdline(&path, &self.envs);
+        let class = CrashClass::classify(san_stderr, exec_status);
+        let bundle = depot::CrashBundle::new(
+            id,
+            exec_status,
+            bucket,
+            class,
+            cmpid,
+            command,
+            reproducibility,
+        );
+        self.depot.save_crash_report(&path, san_stderr, &bundle);
+    }
+
+    /// Shrink the first crash of a new bucket, keeping it in the same bucket.
+    fn minimize_crash(&mut self, buf: &Vec<u8>, id: usize, bucket: &str) {
+        let min_buf = minimize::tmin(buf, config::CRASH_MIN_EXECS, |candidate| {
+            self.crash_bucket(candidate).as_deref() == Some(bucket)
+        });
+        if min_buf != *buf {
+            info!(
+                "Minimize crash {} in {} from {} to {} bytes",
+                id,
+                bucket,
+                buf.len(),
+                min_buf.len()
+            );
+            self.depot.save_crash_min(id, bucket, &min_buf);
+        }
+    }
+
+    /// The bucket of `buf` as in `do_if_has_new`, if it crashes in both
+    /// the fast and the sanitized programs.
+    fn crash_bucket(&mut self, buf: &Vec<u8>) -> Option<String> {
+        let status = self.run_inner(buf);
+        match status.status {
+            StatusType::Crash => {},
+            StatusType::Error => {
+                self.rebind_forksrv();
+                return None;
+            },
+            _ => return None,
+        }
+        let path_hash = self.branches.path_hash();
+        let (san_status, san_stderr) =
+            self.run_with_san(buf, config::MEM_LIMIT_TRACK, self.cmd.san_time_limit());
+        if san_status.status != StatusType::Crash {
+            return None;
+        }
+        let crash_info = branches::CrashInfo::from_output_string(&san_stderr)
+            .unwrap_or_else(|| branches::CrashInfo::from_status(&status, path_hash));
+        Some(crash_info.bucket_hash())
+    }
+
+    /// Rerun a crash `CRASH_REPRO_RUNS` times in both the fast and the sanitized
+    /// programs, since some crashes only happen now and then.
+    fn check_reproducibility(&mut self, buf: &Vec<u8>) -> depot::Reproducibility {
+        let runs = config::CRASH_REPRO_RUNS;
+        let mut fast_crashes = 0;
+        let mut san_crashes = 0;
+        for _ in 0..runs {
+            match self.run_inner(buf).status {
+                StatusType::Crash => fast_crashes += 1,
+                StatusType::Error => self.rebind_forksrv(),
+                _ => {},
+            }
+            let (san_status, _) =
+                self.run_with_san(buf, config::MEM_LIMIT_TRACK, self.cmd.san_time_limit());
+            if san_status.status == StatusType::Crash {
+                san_crashes += 1;
+            }
+        }
+        debug!(
+            "Crash reproduced {}/{} times in the fast program, {}/{} in the sanitized one",
+            fast_crashes, runs, san_crashes, runs
+        );
+        depot::Reproducibility::new(runs, fast_crashes, san_crashes)
+    }
+
+    fn run_init(&mut self) {
+        self.has_new_path = false;
+        self.local_stats.num_exec.count();
+    }
+
+    fn check_timeout(&mut self, status: StatusType, cond: &mut cond_stmt::CondStmt) -> StatusType {
+        let mut ret_status = status;
+        if ret_status == StatusType::Error {
+            self.rebind_forksrv();
+            ret_status = StatusType::Timeout;
+        }
+
+        if ret_status == StatusType::Timeout {
+            self.tmout_cnt = self.tmout_cnt + 1;
+            if self.tmout_cnt >= config::TMOUT_SKIP {
+                cond.to_timeout();
+                ret_status = StatusType::Skip;
+                self.tmout_cnt = 0;
+            }
+        } else {
+            self.tmout_cnt = 0;
+        };
+
+        ret_status
+    }
+
+    fn run_inner(&mut self, buf: &Vec<u8>) -> ExecStatus {
+        self.write_test(buf);
+
+        self.branches.clear_trace();
+        // Stopping, maybe because the branch table has outgrown the maps.
+        if !self.running.load(Ordering::Relaxed) {
+            return ExecStatus::new(StatusType::Skip);
+        }
+
+        compiler_fence(Ordering::SeqCst);
+        let mut ret_status = if let Some(ref mut fs) = self.forksrv {
+            let net_input = &self.net_input;
+            let time_limit = self.cmd.time_limit;
+            let _net_lock = net_input.as_ref().map(|n| n.lock());
+            fs.run_with(|pid| net_input.as_ref().and_then(|n| n.send(pid, time_limit)))
+        } else {
+            self.run_target(&self.cmd.main, self.cmd.mem_limit, self.cmd.time_limit)
+        };
+        compiler_fence(Ordering::SeqCst);
+
+        if let Some(ref mut cgroup) = self.cgroup {
+            if cgroup.has_new_oom_kill() && ret_status.status == StatusType::Crash {
+                ret_status.status = StatusType::Oom;
+            }
+        }
+
+        // debug_cmpid!(self.t_conds.cond.cmpid, "return status = {:?}", ret_status);
+        ret_status
+    }
+
+    /// Rerun the input that has just run, to time it, and to calibrate it:
+    /// the branches whose counts change in the reruns are volatile, and ignored from now on.
+    fn count_time(&mut self) -> u32 {
+        self.branches.start_calibration();
+        let mut used_t = time::Duration::default();
+        for _ in 0..3 {
+            if self.cmd.is_stdin {
+                self.fd.rewind();
+            }
+            self.branches.clear_trace();
+            let t_start = time::Instant::now();
+            let status = if let Some(ref mut fs) = self.forksrv {
+                let net_input = &self.net_input;
+                let time_limit = self.cmd.time_limit;
+                let _net_lock = net_input.as_ref().map(|n| n.lock());
+                let status =
+                    fs.run_with(|pid| net_input.as_ref().and_then(|n| n.send(pid, time_limit)));
+                if status.status == StatusType::Error {
+                    self.rebind_forksrv();
+                    return defs::SLOW_SPEED;
+                }
+                status
+            } else {
+                self.run_target(&self.cmd.main, self.cmd.mem_limit, self.cmd.time_limit)
+            };
+            used_t += t_start.elapsed();
+            // A rerun that hangs or crashes stops halfway.
+            if status.status == StatusType::Normal {
+                self.branches.mark_volatile();
+            }
+        }
+        // OOM kills here are not about the next run.
+        if let Some(ref mut cgroup) = self.cgroup {
+            cgroup.has_new_oom_kill();
+        }
+        let used_us = (used_t.as_secs() as u32 * 1000_000) + used_t.subsec_nanos() / 1_000;
+        used_us / 3
+    }
+
+    fn track(&mut self, id: usize, buf: &Vec<u8>, speed: u32) -> Vec<cond_stmt::CondStmt> {
+        debug_cmpid!(self.t_conds.cond.cmpid, "Running track");
+
+        let t_now: stats::TimeIns = Default::default();
+
+        self.write_test_file(buf);
+
+        compiler_fence(Ordering::SeqCst);
+        let ret_status = self.run_track();
+        compiler_fence(Ordering::SeqCst);
+
+        if ret_status.status != StatusType::Normal {
+            error!(
+                "Crash or hang while tracking! -- {:?},  id: {}",
+                ret_status, id
+            );
+            return vec![];
+        }
+
+        let cond_list = track::load_track_data(
+            Path::new(&self.cmd.track_path),
+            id as u32,
+            speed,
+            self.cmd.mode.is_pin_mode(),
+            FuzzerConfig::get().enable_exploitation(),
+        );
+
+        debug_cmpid!(
+            self.t_conds.cond.cmpid,
+            "Retrieved {} condition statements",
+            cond_list.len()
+        );
+        // debug_cmpid!(self.t_conds.cond.cmpid, "{:?}", cond_list);
+
+        self.local_stats.track_time += t_now.into();
+        cond_list
+    }
+
+    fn run_track(&mut self) -> ExecStatus {
+        if !self.cmd.track_forksrv {
+            return self.run_target(
+                &self.cmd.track,
+                config::MEM_LIMIT_TRACK,
+                self.cmd.track_time_limit(),
+            );
+        }
+
+        if self.track_forksrv.is_none() {
+            self.rebind_track_forksrv();
+        }
+        let net_input = &self.net_input;
+        let time_limit = self.cmd.track_time_limit();
+        let _net_lock = net_input.as_ref().map(|n| n.lock());
+        let status = self
+            .track_forksrv
+            .as_mut()
+            .unwrap()
+            .run_with(|pid| net_input.as_ref().and_then(|n| n.send(pid, time_limit)));
+        if status.status == StatusType::Error {
+            self.rebind_track_forksrv();
+        }
+        status
+    }
+
+    fn write_test(&mut self, buf: &Vec<u8>) {
+        if let Some(ref mut shm_input) = self.shm_input {
+            shm_input.write_buf(buf);
+        } else {
+            self.write_test_file(buf);
+        }
+    }
+
+    fn write_test_file(&mut self, buf: &Vec<u8>) {
+        if let Some(ref mut net_input) = self.net_input {
+            net_input.set_buf(buf);
+        }
+        self.fd.write_buf(buf);
+        if self.cmd.is_stdin {
+            self.fd.rewind();
+        }
+    }
+
+    // The track and sanitized binaries always read the input file,
+    // which `write_test` skips when inputs go through shared memory.
+    fn sync_test_file(&mut self, buf: &Vec<u8>) {
+        if self.shm_input.is_some() {
+            self.write_test_file(buf);
+        }
+    }
+
+    fn run_target(
+        &self,
+        target: &(String, Vec<String>),
+        mem_limit: u64,
+        time_limit: u64,
+    ) -> ExecStatus {
+        let (rlimit, cgroup) = self.limits(mem_limit);
+        let mut cmd = Command::new(&target.0);
+        let cmd = cmd
+            .args(&target.1)
+            .stdin(Stdio::null())
+            .env_clear()
+            .envs(&self.envs)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .mem_limit(rlimit)
+            .cgroup(cgroup)
+            .setsid()
+            .pipe_stdin(self.fd.as_raw_fd(), self.cmd.is_stdin);
+        let _net_lock = self.net_input.as_ref().map(|n| n.lock());
+        let mut child = cmd.spawn().expect("Could not run target");
+        let _net_conn = self
+            .net_input
+            .as_ref()
+            .and_then(|n| n.send(child.id() as i32, time_limit));
+
+        let timeout = time::Duration::from_millis(time_limit);
+        let ret = match child.wait_timeout(timeout).unwrap() {
+            Some(status) => self.get_exec_status(&status),
+            None => {
+                // Timeout
+                // child hasn't exited yet
+                child.kill().expect("Could not send kill signal to child.");
+                child.wait().expect("Error during waiting for child.");
+                ExecStatus::new(StatusType::Timeout)
+            }
+        };
+        ret
+    }
+
+    fn get_exec_status(&self, exit_status: &process::ExitStatus) -> ExecStatus {
+        let mut ret = ExecStatus::from_exit_status(exit_status, self.cmd.uses_asan);
+        // Pin exits with 128 + signal if the program is killed.
+        if let Some(exit_code) = ret.exit_code {
+            if self.cmd.mode.is_pin_mode() && exit_code > 128 {
+                ret.status = StatusType::Crash;
+                ret.signal = Some(exit_code - 128);
+            }
+        }
+        ret
+    }
+}
+
+impl<S: FastServer> Executor for ForkExecutor<S> {
+    fn new(
+        mut cmd: command::CommandOpt,
+        global_branches: Arc<branches::GlobalBranches>,
+        depot: Arc<depot::Depot>,
+        global_stats: Arc<RwLock<stats::ChartStats>>,
+        running: Arc<AtomicBool>,
+    ) -> Self {
+        // ** Share Memory **
+        let shm_id = format!(
+            "{:?}-{:?}-shm",
+            std::process::id(),
+            std::thread::current().id()
+        );
+        let branches = branches::Branches::new(global_branches, &shm_id);
+        let t_conds = cond_stmt::ShmConds::new();
+
+        // ** Envs **
+        let mut envs = HashMap::new();
+        envs.insert(
+            defs::ASAN_OPTIONS_VAR.to_string(),
+            command::san_options(defs::ASAN_OPTIONS_CONTENT),
+        );
+        envs.insert(
+            defs::MSAN_OPTIONS_VAR.to_string(),
+            command::san_options(defs::MSAN_OPTIONS_CONTENT),
+        );
+        envs.insert(
+            defs::UBSAN_OPTIONS_VAR.to_string(),
+            command::san_options(defs::UBSAN_OPTIONS_CONTENT),
+        );
+        envs.insert(defs::BRANCHES_SHM_ENV_VAR.to_string(), shm_id);
+        envs.insert(
+            defs::COND_STMT_ENV_VAR.to_string(),
+            t_conds.get_id().to_string(),
+        );
+        envs.insert(
+            defs::LD_LIBRARY_PATH_VAR.to_string(),
+            cmd.ld_library.clone(),
+        );
+        envs.insert(
+            defs::TRACK_OUTPUT_VAR.to_string(),
+            cmd.track_path.clone(),
+        );
+        envs.insert(
+            defs::SAN_STDERR_VAR.to_string(),
+            cmd.san_stderr_path.clone(),
+        );
+
+        let shm_input = if cmd.shm_input {
+            let shm_input = ShmInput::new();
+            envs.insert(
+                defs::INPUT_SHM_ENV_VAR.to_string(),
+                shm_input.get_id().to_string(),
+            );
+            Some(shm_input)
+        } else {
+            None
+        };
+
+        let net_input = cmd.net.map(NetInput::new);
+
+        let cgroup = cmd.cgroup.as_ref().and_then(|base| {
+            let path = base.join(format!("angora_{}_{}", process::id(), cmd.id));
+            match Cgroup::new(path, cmd.mem_limit, config::CGROUP_PIDS_MAX) {
+                Ok(cgroup) => Some(cgroup),
+                Err(e) => {
+                    warn!("Could not create cgroup, fall back to rlimit: {:?}", e);
+                    None
+                },
+            }
+        });
+        if cmd.cgroup.is_some() && cgroup.is_none() && cmd.uses_asan {
+            cmd.mem_limit = 0;
+        }
+
+        let fd = pipe_fd::PipeFd::new(&cmd.out_file);
+        let forksrv = Some(S::start(
+            &cmd.forksrv_socket_path,
+            &cmd.main,
+            &envs,
+            fd.as_raw_fd(),
+            cmd.is_stdin,
+            cmd.uses_asan,
+            cmd.time_limit,
+            if cgroup.is_some() { 0 } else { cmd.mem_limit },
+            cgroup.as_ref(),
+        ));
+
+        let mut executor = Self {
+            cmd,
+            branches,
+            t_conds,
+            envs,
+            forksrv,
+            track_forksrv: None,
+            san_forksrv: None,
+            san_forksrv_limits: (0, 0),
+            depot,
+            fd,
+            cgroup,
+            shm_input,
+            net_input,
+            tmout_cnt: 0,
+            invariable_cnt: 0,
+            max_exec_time: 0,
+            origin: Default::default(),
+            last_f: defs::UNREACHABLE,
+            has_new_path: false,
+            global_stats,
+            local_stats: Default::default(),
+            running,
+        };
+        executor.resize_branches();
+        executor
+    }
+
+    fn set_time_limit(&mut self, time_limit: u64) {
+        self.cmd.time_limit = time_limit;
+        self.rebind_forksrv();
+        if self.track_forksrv.is_some() {
+            self.rebind_track_forksrv();
+        }
+        // Restarted with the new limit on the next crash.
+        self.san_forksrv = None;
+    }
+
+    fn time_limit(&self) -> u64 {
+        self.cmd.time_limit
+    }
+
+    fn max_exec_time(&self) -> u32 {
+        self.max_exec_time
+    }
+
+    fn run(&mut self, buf: &Vec<u8>, cond: &mut cond_stmt::CondStmt) -> StatusType {
+        self.run_init();
+        self.origin = depot::Origin::from_cond(cond);
+        let status = self.run_inner(buf);
+        self.do_if_has_new(buf, status, false, 0);
+        self.check_timeout(status.status, cond)
+    }
+
+    fn run_with_cond(
+        &mut self,
+        buf: &Vec<u8>,
+        cond: &mut cond_stmt::CondStmt,
+    ) -> (StatusType, i128) {
+        self.run_init();
+        self.origin = depot::Origin::from_cond(cond);
+        self.t_conds.set(cond);
+        let exec_status = self.run_inner(buf);
+        let mut status = exec_status.status;
+
+        let output = self.t_conds.get_cond_output();
+        let mut explored = false;
+        let mut skip = false;
+        skip |= self.check_explored(cond, status, output, &mut explored);
+        skip |= self.check_invariable(output, cond);
+        self.check_consistent(output, cond);
+
+        self.do_if_has_new(buf, exec_status, explored, cond.base.cmpid);
+        status = self.check_timeout(status, cond);
+
+        if skip {
+            status = StatusType::Skip;
+        }
+
+        (status, output)
+    }
+
+    fn run_sync(&mut self, buf: &Vec<u8>) {
+        self.run_init();
+        debug_cmpid!(self.t_conds.cond.cmpid, "Syncing");
+        self.origin = depot::Origin::sync();
+        let status = self.run_inner(buf);
+        self.do_if_has_new(buf, status, false, 0);
+    }
+
+    fn update_log(&mut self) {
+        self.global_stats
+            .write()
+            .unwrap()
+            .sync_from_local(&mut self.local_stats);
+
+        self.t_conds.clear();
+        self.tmout_cnt = 0;
+        self.invariable_cnt = 0;
+        self.last_f = defs::UNREACHABLE;
+    }
+
+    fn random_input_buf(&self) -> Vec<u8> {
+        let id = self.depot.next_random();
+        self.depot.get_input_buf(id)
+    }
+
+    fn restart(&mut self) {
+        self.rebind_forksrv();
+    }
+
+    fn has_new_path(&self) -> bool {
+        self.has_new_path
+    }
+
+    fn last_f(&self) -> i128 {
+        self.last_f
+    }
+
+    fn local_stats(&mut self) -> &mut stats::LocalStats {
+        &mut self.local_stats
+    }
+}
+
+/// Whether the sanitized rerun `san_status` reproduces the crash `status`,
+/// which becomes an OOM on evidence: the allocator reports it, or the sanitized
+/// program runs normally and `needs_mem_limit` tells that the fast one does too without the limit.
+fn confirm_crash<F: FnOnce() -> bool>(
+    status: &mut ExecStatus,
+    san_status: &ExecStatus,
+    needs_mem_limit: F,
+) -> bool {
+    status.sanitizer = san_status.sanitizer;
+    if san_status.status == StatusType::Oom
+        || (san_status.status == StatusType::Normal && needs_mem_limit())
+    {
+        status.status = StatusType::Oom;
+        return false;
+    }
+    san_status.status == status.status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn san_status(wait_status: i32, report: &str) -> ExecStatus {
+        let mut status = ExecStatus::from_wait_status(wait_status, true);
+        status.update_from_san_report(report);
+        status
+    }
+
+    #[test]
+    fn test_confirm_crash() {
+        let no_rerun = || -> bool { panic!("The crash needs no rerun") };
+
+        // UBSan aborts on the first error.
+        let mut status = ExecStatus::from_wait_status(libc::SIGSEGV, false);
+        let ubsan = san_status(
+            libc::SIGABRT,
+            "/src/a.c:12:3: runtime error: signed integer overflow: 2147483647 + 1 cannot be represented in type 'int'",
+        );
+        assert!(confirm_crash(&mut status, &ubsan, no_rerun));
+        assert_eq!(status.status, StatusType::Crash);
+        assert_eq!(status.sanitizer, Some(SanitizerKind::Ubsan));
+
+        // So does LSan, with detect_leaks=1.
+        let mut status = ExecStatus::from_wait_status(libc::SIGABRT, true);
+        let lsan = san_status(
+            libc::SIGABRT,
+            "==1==ERROR: LeakSanitizer: detected memory leaks
+
+Direct leak of 8 byte(s) in 1 object(s) allocated from:
+    #0 0x4f4a1b in malloc
+    #1 0x4f4b2c in parse /src/a.c:12:3",
+        );
+        assert!(confirm_crash(&mut status, &lsan, no_rerun));
+        assert_eq!(status.sanitizer, Some(SanitizerKind::Lsan));
+
+        let mut status = ExecStatus::from_wait_status(libc::SIGSEGV, false);
+        let oom = san_status(
+            libc::SIGABRT,
+            "==1==ERROR: AddressSanitizer: requested allocation size 0x10000000000 exceeds maximum",
+        );
+        assert!(!confirm_crash(&mut status, &oom, no_rerun));
+        assert_eq!(status.status, StatusType::Oom);
+    }
+}
@@ -1,4 +1,4 @@
-use super::{limit::SetLimit, *};
+use super::{cgroup::Cgroup, limit::SetLimit, *};
 use angora_common::defs::*;
 use byteorder::{LittleEndian, ReadBytesExt};
 use libc;
@@ -18,6 +18,29 @@ use std::{
 // Just meaningless value for forking a new child
 static FORKSRV_NEW_CHILD: [u8; 4] = [8, 8, 8, 8];
 
+/// The fast program, which stays up to run one test case after another.
+/// `ForkExecutor` starts it again when a run returns `StatusType::Error`.
+pub trait FastServer {
+    // The arguments of `Forksrv::new`.
+    #[allow(clippy::too_many_arguments)]
+    fn start(
+        socket_path: &str,
+        target: &(String, Vec<String>),
+        envs: &HashMap<String, String>,
+        fd: RawFd,
+        is_stdin: bool,
+        uses_asan: bool,
+        time_limit: u64,
+        mem_limit: u64,
+        cgroup: Option<&Cgroup>,
+    ) -> Self
+    where
+        Self: Sized;
+    /// Call `deliver` with the pid of the process that runs the test case,
+    /// and keep what it returns until the test case has finished.
+    fn run_with<T, F: FnOnce(i32) -> T>(&mut self, deliver: F) -> ExecStatus;
+}
+
 #[derive(Debug)]
 pub struct Forksrv {
     path: String,
@@ -36,6 +59,7 @@ impl Forksrv {
         uses_asan: bool,
         time_limit: u64,
         mem_limit: u64,
+        cgroup: Option<&Cgroup>,
     ) -> Forksrv {
         info!("socket_path: {:?}", socket_path);
         let listener = match UnixListener::bind(socket_path) {
@@ -56,6 +80,7 @@ impl Forksrv {
             .stdout(Stdio::null())
             .stderr(Stdio::null())
             .mem_limit(mem_limit.clone())
+            .cgroup(cgroup)
             .setsid()
             .pipe_stdin(fd, is_stdin)
             .spawn()
@@ -77,10 +102,10 @@ impl Forksrv {
         };
 
         socket
-            .set_read_timeout(Some(Duration::from_secs(time_limit)))
+            .set_read_timeout(Some(Duration::from_millis(time_limit)))
             .expect("Couldn't set read timeout");
         socket
-            .set_write_timeout(Some(Duration::from_secs(time_limit)))
+            .set_write_timeout(Some(Duration::from_millis(time_limit)))
             .expect("Couldn't set write timeout");
 
         info!("All right -- Init ForkServer {} successfully!", socket_path);
@@ -93,10 +118,16 @@ impl Forksrv {
         }
     }
 
-    pub fn run(&mut self) -> StatusType {
+    pub fn run(&mut self) -> ExecStatus {
+        self.run_with(|_| ())
+    }
+
+    /// Call `deliver` with the pid of the child once it has started,
+    /// and keep what it returns until the child has finished.
+    pub fn run_with<T, F: FnOnce(i32) -> T>(&mut self, deliver: F) -> ExecStatus {
         if self.socket.write(&FORKSRV_NEW_CHILD).is_err() {
             warn!("Fail to write socket!!");
-            return StatusType::Error;
+            return ExecStatus::new(StatusType::Error);
         }
 
         let mut buf = vec![0; 4];
@@ -107,7 +138,7 @@ impl Forksrv {
                     Ok(a) => a,
                     Err(e) => {
                         warn!("Unable to recover child pid: {:?}", e);
-                        return StatusType::Error;
+                        return ExecStatus::new(StatusType::Error);
                     },
                 };
                 if child_pid <= 0 {
@@ -115,15 +146,17 @@ impl Forksrv {
                         "Unable to request new process from fork server! {}",
                         child_pid
                     );
-                    return StatusType::Error;
+                    return ExecStatus::new(StatusType::Error);
                 }
             },
             Err(error) => {
                 warn!("Fail to read child_id -- {}", error);
-                return StatusType::Error;
+                return ExecStatus::new(StatusType::Error);
             },
         }
 
+        let _delivered = deliver(child_pid);
+
         buf = vec![0; 4];
 
         let read_result = self.socket.read(&mut buf);
@@ -134,17 +167,19 @@ impl Forksrv {
                     Ok(a) => a,
                     Err(e) => {
                         warn!("Unable to recover result from child: {}", e);
-                        return StatusType::Error;
+                        return ExecStatus::new(StatusType::Error);
                     },
                 };
-                let exit_code = libc::WEXITSTATUS(status);
-                let signaled = libc::WIFSIGNALED(status);
-                if signaled || (self.uses_asan && exit_code == MSAN_ERROR_CODE) {
+                // A persistent-mode child stops itself after each loop iteration
+                // and waits to be resumed for the next test case.
+                if libc::WIFSTOPPED(status) {
+                    return ExecStatus::new(StatusType::Normal);
+                }
+                let exec_status = ExecStatus::from_wait_status(status, self.uses_asan);
+                if exec_status.status == StatusType::Crash {
                     trace!("Crash code: {}", status);
-                    StatusType::Crash
-                } else {
-                    StatusType::Normal
                 }
+                exec_status
             },
 
             Err(_) => {
@@ -155,12 +190,42 @@ impl Forksrv {
                 while let Err(_) = self.socket.read(tmout_buf) {
                     warn!("Killing timed out process");
                 }
-                return StatusType::Timeout;
+                return ExecStatus::new(StatusType::Timeout);
             },
         }
     }
 }
 
+impl FastServer for Forksrv {
+    fn start(
+        socket_path: &str,
+        target: &(String, Vec<String>),
+        envs: &HashMap<String, String>,
+        fd: RawFd,
+        is_stdin: bool,
+        uses_asan: bool,
+        time_limit: u64,
+        mem_limit: u64,
+        cgroup: Option<&Cgroup>,
+    ) -> Self {
+        Forksrv::new(
+            socket_path,
+            target,
+            envs,
+            fd,
+            is_stdin,
+            uses_asan,
+            time_limit,
+            mem_limit,
+            cgroup,
+        )
+    }
+
+    fn run_with<T, F: FnOnce(i32) -> T>(&mut self, deliver: F) -> ExecStatus {
+        Forksrv::run_with(self, deliver)
+    }
+}
+
 impl Drop for Forksrv {
     fn drop(&mut self) {
         info!("Exit Forksrv");
@@ -0,0 +1,171 @@
+use super::{cgroup::Cgroup, fork_executor::ForkExecutor, forksrv::FastServer, limit::SetLimit, *};
+use angora_common::defs::*;
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, prelude::*},
+    os::unix::{
+        io::RawFd,
+        net::{UnixListener, UnixStream},
+        process::ExitStatusExt,
+    },
+    path::Path,
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+/// Runs a libFuzzer harness in `libfuzzer_loader`, which calls `LLVMFuzzerTestOneInput`
+/// on one test case after another in the same process, like libFuzzer does.
+pub type LibFuzzerExecutor = ForkExecutor<HarnessWorker>;
+
+// Asks the worker to run the test case in the shared memory.
+static WORKER_RUN: [u8; 4] = [8, 8, 8, 8];
+
+/// The loader process that runs the test cases. It is started again
+/// after a test case has crashed it or has been killed for hanging.
+pub struct HarnessWorker {
+    path: String,
+    listener: UnixListener,
+    // Kept to spawn the worker again with the same limits.
+    command: Command,
+    child: Option<(Child, UnixStream)>,
+    uses_asan: bool,
+    time_limit: u64,
+}
+
+impl HarnessWorker {
+    fn spawn(&mut self) -> io::Result<()> {
+        let child = self.command.spawn()?;
+        // FIXME: block here if the loader doesn't connect.
+        let (socket, _) = self.listener.accept()?;
+        socket.set_read_timeout(Some(Duration::from_millis(self.time_limit)))?;
+        socket.set_write_timeout(Some(Duration::from_millis(self.time_limit)))?;
+        self.child = Some((child, socket));
+        Ok(())
+    }
+
+    /// Wait for the worker, which has exited or been killed, and return how.
+    fn reap(&mut self) -> Option<i32> {
+        let (mut child, _) = self.child.take()?;
+        child.wait().ok().map(|status| status.into_raw())
+    }
+
+    fn kill(&mut self) {
+        if let Some((ref mut child, _)) = self.child {
+            let _ = child.kill();
+        }
+        self.reap();
+    }
+}
+
+impl FastServer for HarnessWorker {
+    fn start(
+        socket_path: &str,
+        target: &(String, Vec<String>),
+        envs: &HashMap<String, String>,
+        fd: RawFd,
+        is_stdin: bool,
+        uses_asan: bool,
+        time_limit: u64,
+        mem_limit: u64,
+        cgroup: Option<&Cgroup>,
+    ) -> Self {
+        info!("socket_path: {:?}", socket_path);
+        let listener = match UnixListener::bind(socket_path) {
+            Ok(sock) => sock,
+            Err(e) => {
+                error!("FATAL: Failed to bind to socket: {:?}", e);
+                panic!();
+            },
+        };
+
+        let mut command = Command::new(&target.0);
+        command
+            .args(&target.1)
+            .stdin(Stdio::null())
+            .envs(envs)
+            .env(WORKER_SOCKET_PATH_VAR, socket_path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .mem_limit(mem_limit)
+            .cgroup(cgroup)
+            .setsid()
+            .pipe_stdin(fd, is_stdin);
+
+        let mut worker = Self {
+            path: socket_path.to_owned(),
+            listener,
+            command,
+            child: None,
+            uses_asan,
+            time_limit,
+        };
+        if let Err(e) = worker.spawn() {
+            error!("FATAL: Failed to start the harness worker: {:?}", e);
+            panic!();
+        }
+        info!(
+            "All right -- Init harness worker {} successfully!",
+            socket_path
+        );
+        worker
+    }
+
+    fn run_with<T, F: FnOnce(i32) -> T>(&mut self, deliver: F) -> ExecStatus {
+        if self.child.is_none() {
+            if let Err(e) = self.spawn() {
+                warn!("Fail to restart the harness worker: {:?}", e);
+                return ExecStatus::new(StatusType::Error);
+            }
+        }
+        let (child, socket) = self.child.as_mut().unwrap();
+        let pid = child.id() as i32;
+
+        if socket.write_all(&WORKER_RUN).is_err() {
+            warn!("Fail to write socket!!");
+            self.kill();
+            return ExecStatus::new(StatusType::Error);
+        }
+        // The worker answers with its pid once it has started the test case.
+        if socket.read_i32::<LittleEndian>().is_err() {
+            warn!("Fail to read the pid of the harness worker");
+            self.kill();
+            return ExecStatus::new(StatusType::Error);
+        }
+
+        let _delivered = deliver(pid);
+
+        match socket.read_i32::<LittleEndian>() {
+            Ok(_) => ExecStatus::new(StatusType::Normal),
+            Err(ref e)
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut =>
+            {
+                self.kill();
+                ExecStatus::new(StatusType::Timeout)
+            },
+            // The test case has brought the worker down.
+            Err(_) => match self.reap() {
+                Some(status) => {
+                    let exec_status = ExecStatus::from_wait_status(status, self.uses_asan);
+                    if exec_status.status == StatusType::Crash {
+                        trace!("Crash code: {}", status);
+                    }
+                    exec_status
+                },
+                None => ExecStatus::new(StatusType::Error),
+            },
+        }
+    }
+}
+
+impl Drop for HarnessWorker {
+    fn drop(&mut self) {
+        info!("Exit harness worker");
+        self.kill();
+        let path = Path::new(&self.path);
+        if path.exists() && fs::remove_file(path).is_err() {
+            warn!("Fail to remove socket file!!  FIN ");
+        }
+    }
+}
@@ -1,5 +1,7 @@
+use super::cgroup::Cgroup;
 use libc;
 use std::{
+    io,
     os::unix::{io::RawFd, process::CommandExt},
     process::Command,
 };
@@ -8,6 +10,7 @@ pub trait SetLimit {
     fn mem_limit(&mut self, size: u64) -> &mut Self;
     fn setsid(&mut self) -> &mut Self;
     fn pipe_stdin(&mut self, fd: RawFd, is_stdin: bool) -> &mut Self;
+    fn cgroup(&mut self, cgroup: Option<&Cgroup>) -> &mut Self;
     // fn dup2(&mut self, src: libc::c_int, dst: libc::c_int) -> &mut Self;
     // fn close_fd(&mut self, fd: libc::c_int) -> &mut Self;
 }
@@ -70,4 +73,28 @@ impl SetLimit for Command {
             self
         }
     }
+
+    fn cgroup(&mut self, cgroup: Option<&Cgroup>) -> &mut Self {
+        if let Some(cgroup) = cgroup {
+            let procs = cgroup.procs_path().to_owned();
+            let func = move || {
+                // Writing "0" moves the writer itself.
+                unsafe {
+                    let fd = libc::open(procs.as_ptr(), libc::O_WRONLY);
+                    if fd < 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    let ret = libc::write(fd, b"0".as_ptr() as *const libc::c_void, 1);
+                    libc::close(fd);
+                    if ret != 1 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            };
+            unsafe { self.pre_exec(func) }
+        } else {
+            self
+        }
+    }
 }
@@ -1,8 +1,19 @@
+pub mod cgroup;
 mod executor;
+mod fork_executor;
 pub mod forksrv;
+mod libfuzzer_executor;
 mod limit;
+pub mod net;
 pub mod pipe_fd;
+mod shm_input;
 mod status_type;
 
-use self::pipe_fd::PipeFd;
-pub use self::{executor::Executor, forksrv::Forksrv, status_type::StatusType};
+use self::{net::NetInput, pipe_fd::PipeFd, shm_input::ShmInput};
+pub use self::{
+    executor::Executor,
+    fork_executor::ForkExecutor,
+    forksrv::{FastServer, Forksrv},
+    libfuzzer_executor::{HarnessWorker, LibFuzzerExecutor},
+    status_type::{ExecStatus, SanitizerKind, StatusType},
+};
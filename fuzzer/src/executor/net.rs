@@ -0,0 +1,226 @@
+use lazy_static::lazy_static;
+use libc;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, prelude::*},
+    net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+    sync::{Mutex, MutexGuard},
+    thread,
+    time::{Duration, Instant},
+};
+
+// How long to wait before retrying while the target is not listening yet.
+static RETRY_INTERVAL: Duration = Duration::from_millis(1);
+
+lazy_static! {
+    // Only one target at a time can listen on a port, so executors sharing it take turns.
+    static ref NET_LOCKS: Mutex<HashMap<SocketAddr, &'static Mutex<()>>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NetProto {
+    Tcp,
+    Udp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NetOpt {
+    pub proto: NetProto,
+    pub addr: SocketAddr,
+}
+
+impl NetOpt {
+    /// Parse "tcp://host:port" or "udp://host:port".
+    pub fn parse(net: &str) -> Self {
+        let (proto, addr) = if let Some(addr) = net.strip_prefix("tcp://") {
+            (NetProto::Tcp, addr)
+        } else if let Some(addr) = net.strip_prefix("udp://") {
+            (NetProto::Udp, addr)
+        } else {
+            panic!("Network address should be tcp://HOST:PORT or udp://HOST:PORT.");
+        };
+        let addr = addr
+            .to_socket_addrs()
+            .expect("Could not resolve network address.")
+            .next()
+            .expect("Could not resolve network address.");
+        Self { proto, addr }
+    }
+}
+
+// Keeps the connection open until the target has finished.
+pub enum NetConn {
+    Tcp(TcpStream),
+    Udp(UdpSocket),
+}
+
+/// Sends the current input to the port the target listens on.
+pub struct NetInput {
+    opt: NetOpt,
+    buf: Vec<u8>,
+}
+
+impl NetInput {
+    pub fn new(opt: NetOpt) -> Self {
+        Self { opt, buf: vec![] }
+    }
+
+    pub fn set_buf(&mut self, buf: &[u8]) {
+        self.buf.clear();
+        self.buf.extend_from_slice(buf);
+    }
+
+    /// Hold it from before the target starts until it ends.
+    pub fn lock(&self) -> MutexGuard<'static, ()> {
+        let lock = *NET_LOCKS
+            .lock()
+            .unwrap()
+            .entry(self.opt.addr)
+            .or_insert_with(|| Box::leak(Box::new(Mutex::new(()))));
+        lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Retry until the process `pid` listens on the port, dies or the time is up.
+    pub fn send(&self, pid: i32, time_limit: u64) -> Option<NetConn> {
+        let deadline = Instant::now() + Duration::from_millis(time_limit);
+        loop {
+            let res = match self.opt.proto {
+                NetProto::Tcp => self.send_tcp(deadline).map(NetConn::Tcp),
+                NetProto::Udp => self.send_udp().map(NetConn::Udp),
+            };
+            match res {
+                Ok(conn) => return Some(conn),
+                Err(e) => {
+                    let alive = unsafe { libc::kill(pid, 0) } == 0;
+                    if !alive || Instant::now() + RETRY_INTERVAL >= deadline {
+                        debug!("Could not send input to {}: {:?}", self.opt.addr, e);
+                        return None;
+                    }
+                    thread::sleep(RETRY_INTERVAL);
+                },
+            }
+        }
+    }
+
+    fn send_tcp(&self, deadline: Instant) -> io::Result<TcpStream> {
+        let timeout = deadline
+            .saturating_duration_since(Instant::now())
+            .max(RETRY_INTERVAL);
+        let mut stream = TcpStream::connect_timeout(&self.opt.addr, timeout)?;
+        stream.set_write_timeout(Some(timeout))?;
+        stream.write_all(&self.buf)?;
+        // The target sees EOF after the input.
+        stream.shutdown(Shutdown::Write)?;
+        Ok(stream)
+    }
+
+    fn send_udp(&self) -> io::Result<UdpSocket> {
+        // A datagram sent before the target binds the port is lost.
+        if !udp_bound(self.opt.addr.port()) {
+            return Err(io::ErrorKind::NotConnected.into());
+        }
+        let local: SocketAddr = if self.opt.addr.is_ipv4() {
+            "0.0.0.0:0".parse().unwrap()
+        } else {
+            "[::]:0".parse().unwrap()
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(self.opt.addr)?;
+        socket.send(&self.buf)?;
+        Ok(socket)
+    }
+}
+
+/// Whether a UDP socket is bound to `port`, from the tables of the kernel.
+fn udp_bound(port: u16) -> bool {
+    ["/proc/net/udp", "/proc/net/udp6"].iter().any(|table| {
+        fs::read_to_string(table)
+            .map(|table| {
+                // "sl local_address rem_address st ...", with "ADDR:PORT" in hex.
+                table.lines().skip(1).any(|line| {
+                    line.split_whitespace()
+                        .nth(1)
+                        .and_then(|local| local.rsplit(':').next())
+                        .and_then(|p| u16::from_str_radix(p, 16).ok())
+                        == Some(port)
+                })
+            })
+            .unwrap_or(false)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_net() {
+        let opt = NetOpt::parse("tcp://127.0.0.1:8080");
+        assert_eq!(opt.proto, NetProto::Tcp);
+        assert_eq!(opt.addr, "127.0.0.1:8080".parse().unwrap());
+        let opt = NetOpt::parse("udp://127.0.0.1:53");
+        assert_eq!(opt.proto, NetProto::Udp);
+    }
+
+    // A port that nothing listens on, until the echo target binds it.
+    fn free_port() -> u16 {
+        UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    fn echo_input(proto: &str, port: u16) -> NetInput {
+        let mut net_input =
+            NetInput::new(NetOpt::parse(&format!("{}://127.0.0.1:{}", proto, port)));
+        net_input.set_buf(b"hello");
+        net_input
+    }
+
+    #[test]
+    fn test_send_tcp() {
+        let port = free_port();
+        // Starts listening late, as a target that is still starting up.
+        let target = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            let listener = std::net::TcpListener::bind(("127.0.0.1", port)).unwrap();
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = vec![];
+            stream.read_to_end(&mut buf).unwrap();
+            stream.write_all(&buf).unwrap();
+        });
+        let net_input = echo_input("tcp", port);
+        let _lock = net_input.lock();
+        let conn = net_input.send(std::process::id() as i32, 5000);
+        let mut buf = vec![];
+        match conn {
+            Some(NetConn::Tcp(mut stream)) => stream.read_to_end(&mut buf).unwrap(),
+            _ => panic!("Could not send the input"),
+        };
+        assert_eq!(buf, b"hello");
+        target.join().unwrap();
+    }
+
+    #[test]
+    fn test_send_udp() {
+        let port = free_port();
+        let target = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            let socket = UdpSocket::bind(("127.0.0.1", port)).unwrap();
+            let mut buf = [0; 16];
+            let (len, peer) = socket.recv_from(&mut buf).unwrap();
+            socket.send_to(&buf[..len], peer).unwrap();
+        });
+        let net_input = echo_input("udp", port);
+        let conn = net_input.send(std::process::id() as i32, 5000);
+        let mut buf = [0; 16];
+        let len = match conn {
+            Some(NetConn::Udp(socket)) => socket.recv(&mut buf).unwrap(),
+            _ => panic!("Could not send the input"),
+        };
+        assert_eq!(&buf[..len], b"hello");
+        target.join().unwrap();
+    }
+}
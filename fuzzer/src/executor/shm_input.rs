@@ -0,0 +1,28 @@
+use angora_common::{input_buf::InputBuf, shm};
+
+// Test case delivery through shared memory, read by
+// `__ANGORA_FUZZ_TESTCASE_BUF` in the fast binary.
+pub struct ShmInput {
+    input: shm::SHM<InputBuf>,
+}
+
+impl ShmInput {
+    pub fn new() -> Self {
+        let mut input = shm::SHM::<InputBuf>::new();
+        input.len = 0;
+        Self { input }
+    }
+
+    pub fn get_id(&self) -> i32 {
+        self.input.get_id()
+    }
+
+    pub fn write_buf(&mut self, buf: &[u8]) {
+        if self.input.set(buf) < buf.len() {
+            warn!(
+                "Input of {} bytes is truncated in shared memory delivery.",
+                buf.len()
+            );
+        }
+    }
+}
@@ -1,8 +1,137 @@
+use crate::crash_report;
+use angora_common::defs;
+use libc;
+use std::{os::unix::process::ExitStatusExt, process::ExitStatus};
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum StatusType {
     Normal,
     Timeout,
     Crash,
+    // Crashed because an allocation failed under the memory limit.
+    Oom,
     Skip,
     Error,
 }
+
+// Reports that a sanitizer gives for allocation failures rather than memory errors.
+static SAN_OOM_MSGS: [&str; 4] = [
+    "out of memory",
+    "out-of-memory",
+    "allocation-size-too-big",
+    "requested allocation size",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SanitizerKind {
+    Asan,
+    Msan,
+    Ubsan,
+    Lsan,
+}
+
+/// The `StatusType` of an execution and how the child ended.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExecStatus {
+    pub status: StatusType,
+    pub signal: Option<i32>,
+    pub exit_code: Option<i32>,
+    pub sanitizer: Option<SanitizerKind>,
+}
+
+impl ExecStatus {
+    pub fn new(status: StatusType) -> Self {
+        Self {
+            status,
+            signal: None,
+            exit_code: None,
+            sanitizer: None,
+        }
+    }
+
+    fn from_signal(signal: i32) -> Self {
+        Self {
+            signal: Some(signal),
+            ..Self::new(StatusType::Crash)
+        }
+    }
+
+    fn from_exit_code(exit_code: i32, uses_asan: bool) -> Self {
+        // MSan exits with its own code instead of a signal.
+        if uses_asan && exit_code == defs::MSAN_ERROR_CODE {
+            Self {
+                exit_code: Some(exit_code),
+                sanitizer: Some(SanitizerKind::Msan),
+                ..Self::new(StatusType::Crash)
+            }
+        } else {
+            Self {
+                exit_code: Some(exit_code),
+                ..Self::new(StatusType::Normal)
+            }
+        }
+    }
+
+    /// From the status returned by `waitpid`.
+    pub fn from_wait_status(wait_status: i32, uses_asan: bool) -> Self {
+        if libc::WIFSIGNALED(wait_status) {
+            Self::from_signal(libc::WTERMSIG(wait_status))
+        } else {
+            Self::from_exit_code(libc::WEXITSTATUS(wait_status), uses_asan)
+        }
+    }
+
+    pub fn from_exit_status(exit_status: &ExitStatus, uses_asan: bool) -> Self {
+        match exit_status.code() {
+            Some(exit_code) => Self::from_exit_code(exit_code, uses_asan),
+            None => Self::from_signal(exit_status.signal().unwrap_or(0)),
+        }
+    }
+
+    pub fn is_abort(&self) -> bool {
+        self.signal == Some(libc::SIGABRT)
+    }
+
+    pub fn is_segv(&self) -> bool {
+        self.signal == Some(libc::SIGSEGV) || self.signal == Some(libc::SIGBUS)
+    }
+
+    /// Fill in the sanitizer and OOMs from the stderr of a sanitized run.
+    pub fn update_from_san_report(&mut self, report: &str) {
+        if let Some(parsed) = crash_report::parse(report) {
+            self.sanitizer = Some(parsed.sanitizer);
+        }
+        if self.sanitizer.is_some() && SAN_OOM_MSGS.iter().any(|msg| report.contains(msg)) {
+            self.status = StatusType::Oom;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wait_status() {
+        // killed by SIGSEGV
+        let st = ExecStatus::from_wait_status(libc::SIGSEGV, false);
+        assert_eq!(st.status, StatusType::Crash);
+        assert!(st.is_segv() && !st.is_abort());
+        // exit(86)
+        let st = ExecStatus::from_wait_status(defs::MSAN_ERROR_CODE << 8, true);
+        assert_eq!(st.status, StatusType::Crash);
+        assert_eq!(st.sanitizer, Some(SanitizerKind::Msan));
+        let st = ExecStatus::from_wait_status(defs::MSAN_ERROR_CODE << 8, false);
+        assert_eq!(st.status, StatusType::Normal);
+    }
+
+    #[test]
+    fn test_san_report() {
+        let mut st = ExecStatus::from_wait_status(libc::SIGABRT, true);
+        st.update_from_san_report(
+            "==1==ERROR: AddressSanitizer: requested allocation size 0x10000000000 exceeds maximum",
+        );
+        assert_eq!(st.sanitizer, Some(SanitizerKind::Asan));
+        assert_eq!(st.status, StatusType::Oom);
+    }
+}
@@ -12,6 +12,8 @@ use std::{
     time::Instant,
 };
 
+#[cfg(debug_assertions)]
+use crate::source_map;
 #[cfg(debug_assertions)]
 use lazy_static::lazy_static;
 #[cfg(debug_assertions)]
@@ -24,7 +26,7 @@ lazy_static! {
     };
 }
 
-pub fn fuzz_loop(
+pub fn fuzz_loop<E: Executor>(
     running: Arc<AtomicBool>,
     cmd_opt: CommandOpt,
     depot: Arc<Depot>,
@@ -32,11 +34,12 @@ pub fn fuzz_loop(
     global_stats: Arc<RwLock<stats::ChartStats>>,
 ) {
     // let search_method = cmd_opt.search_method;
-    let mut executor = Executor::new(
+    let mut executor = E::new(
         cmd_opt,
         global_branches,
         depot.clone(),
         global_stats.clone(),
+        running.clone(),
     );
 
     info!(
@@ -139,8 +142,11 @@ pub fn fuzz_loop(
         {
             let mills = now.elapsed().as_millis();
             info!(
-                "cmpid: 0x{:08x}, type: {:?}, priority: {}, elapsed: {:1}.{:03}s, solved: {}",
+                "cmpid: 0x{:08x}{}, type: {:?}, priority: {}, elapsed: {:1}.{:03}s, solved: {}",
                 cond.base.cmpid,
+                source_map::cmpid_loc(cond.base.cmpid)
+                    .map(|loc| format!(" at {}", loc))
+                    .unwrap_or_default(),
                 cond.get_fuzz_type(),
                 priority.get(),
                 mills / 1000,
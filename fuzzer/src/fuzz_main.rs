@@ -1,5 +1,7 @@
 use crate::{
-    bind_cpu, branches, check_dep, command, depot, executor, fuzz_loop,
+    bind_cpu, branches, check_dep, command, depot,
+    executor::{self, Executor},
+    fuzz_loop, source_map,
     stats::{self, *},
 };
 use angora_common::{config::FuzzerConfig, defs};
@@ -7,10 +9,11 @@ use chrono::prelude::Local;
 use ctrlc;
 use libc;
 use pretty_env_logger;
+use serde_derive::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     fs,
-    io::prelude::*,
+    io::{self, prelude::*},
     path::{Path, PathBuf},
     sync::{
         atomic::{AtomicBool, AtomicUsize, Ordering},
@@ -19,6 +22,41 @@ use std::{
     thread, time,
 };
 
+// A libFuzzer harness runs in its own executor.
+fn new_executor(
+    cmd: command::CommandOpt,
+    global_branches: Arc<branches::GlobalBranches>,
+    depot: Arc<depot::Depot>,
+    stats: Arc<RwLock<stats::ChartStats>>,
+    running: Arc<AtomicBool>,
+) -> Box<dyn Executor> {
+    if cmd.libfuzzer.is_some() {
+        Box::new(executor::LibFuzzerExecutor::new(
+            cmd,
+            global_branches,
+            depot,
+            stats,
+            running,
+        ))
+    } else {
+        Box::new(<executor::ForkExecutor>::new(
+            cmd,
+            global_branches,
+            depot,
+            stats,
+            running,
+        ))
+    }
+}
+
+// Saved next to `branches/` with the maps, to resume the campaign in place.
+#[derive(Serialize, Deserialize)]
+struct Checkpoint {
+    depot: depot::DepotCheckpoint,
+    stats: stats::StatsCheckpoint,
+    time_limit: u64,
+}
+
 pub fn fuzz_main(
     mode: &str,
     in_dir: &str,
@@ -29,15 +67,22 @@ pub fn fuzz_main(
     bind: Option<usize>,
     num_jobs: usize,
     mem_limit: u64,
-    time_limit: u64,
+    time_limit: &str,
     search_method: &str,
     sync_afl: bool,
+    net: Option<&str>,
+    libfuzzer: bool,
+    cgroup: bool,
+    source_map: Option<&str>,
 ) {
     pretty_env_logger::init();
 
     warn!("Running with config: \n{:#?}", FuzzerConfig::get());
-    let (seeds_dir, angora_out_dir) = initialize_directories(in_dir, out_dir, sync_afl);
-    let command_option = command::CommandOpt::new(
+    if let Some(path) = source_map {
+        source_map::init(Path::new(path));
+    }
+    let (seeds_dir, angora_out_dir, in_place) = initialize_directories(in_dir, out_dir, sync_afl);
+    let mut command_option = command::CommandOpt::new(
         mode,
         track_target,
         san_target,
@@ -46,8 +91,13 @@ pub fn fuzz_main(
         search_method,
         mem_limit,
         time_limit,
+        net,
+        libfuzzer,
+        cgroup,
     );
     info!("{:?}", command_option);
+    // Fail before the dry run if the port of the last job doesn't fit.
+    command_option.specify(num_jobs);
 
     check_dep::check_dep(in_dir, out_dir, &command_option);
 
@@ -60,14 +110,50 @@ pub fn fuzz_main(
     let running = Arc::new(AtomicBool::new(true));
     set_sigint_handler(running.clone());
 
-    let mut executor = executor::Executor::new(
+    let mut executor = new_executor(
         command_option.specify(0),
         global_branches.clone(),
         depot.clone(),
         stats.clone(),
+        running.clone(),
     );
 
-    depot::sync_depot(&mut executor, running.clone(), &depot.dirs.seeds_dir);
+    let checkpoint = if in_place {
+        Some(load_checkpoint(&angora_out_dir))
+    } else {
+        None
+    };
+
+    // Resuming from the old output directory, which the seeds are the queue of.
+    let resume_dir = if in_dir == "-" {
+        depot
+            .dirs
+            .seeds_dir
+            .parent()
+            .map(|dir| dir.join(defs::BRANCHES_DIR))
+    } else {
+        None
+    };
+    if let Some(dir) = &resume_dir {
+        global_branches.load(dir);
+    }
+
+    if let Some(checkpoint) = checkpoint {
+        // The conditions are restored, so the queue need not be rerun.
+        depot.restore(checkpoint.depot);
+        stats.write().unwrap().restore(checkpoint.stats);
+        global_branches.load_virgin(&depot.dirs.branches_dir);
+        if command_option.is_auto_time_limit {
+            command_option.time_limit = checkpoint.time_limit;
+            command_option.is_auto_time_limit = false;
+            executor.set_time_limit(command_option.time_limit);
+        }
+    } else {
+        depot::sync_depot(&mut *executor, running.clone(), &depot.dirs.seeds_dir);
+        if let Some(dir) = &resume_dir {
+            global_branches.load_virgin(dir);
+        }
+    }
 
     if depot.empty() {
         error!("Failed to find any branches during dry run.");
@@ -79,6 +165,16 @@ pub fn fuzz_main(
         panic!();
     }
 
+    if command_option.is_auto_time_limit {
+        command_option.calibrate_time_limit(executor.max_exec_time());
+        executor.set_time_limit(command_option.time_limit);
+        warn!(
+            "Calibrated time limit: {}ms, the slowest seed takes {}us",
+            command_option.time_limit,
+            executor.max_exec_time()
+        );
+    }
+
     let (handles, child_count) = init_cpus_and_run_fuzzing_threads(
         bind,
         num_jobs,
@@ -89,7 +185,11 @@ pub fn fuzz_main(
         &stats,
     );
 
-    let log_file = match fs::File::create(angora_out_dir.join(defs::ANGORA_LOG_FILE)) {
+    let log_file = match fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(angora_out_dir.join(defs::ANGORA_LOG_FILE))
+    {
         Ok(a) => a,
         Err(e) => {
             error!("FATAL: Could not create log file: {:?}", e);
@@ -101,7 +201,7 @@ pub fn fuzz_main(
         out_dir,
         sync_afl,
         running.clone(),
-        &mut executor,
+        &mut *executor,
         &depot,
         &global_branches,
         &stats,
@@ -113,6 +213,10 @@ pub fn fuzz_main(
             error!("Error happened in fuzzing thread!");
         }
     }
+    global_branches.save(&depot.dirs.branches_dir);
+    save_lcov(&depot, &global_branches);
+    save_checkpoint(&depot, &stats, executor.time_limit());
+    depot.save_crash_index();
 
     match fs::remove_file(&fuzzer_stats) {
         Ok(_) => (),
@@ -121,7 +225,8 @@ pub fn fuzz_main(
     warn!("Double check your config: \n{:#?}", FuzzerConfig::get());
 }
 
-fn initialize_directories(in_dir: &str, out_dir: &str, sync_afl: bool) -> (PathBuf, PathBuf) {
+/// Also tells whether the campaign is resumed in place, from its checkpoint.
+fn initialize_directories(in_dir: &str, out_dir: &str, sync_afl: bool) -> (PathBuf, PathBuf, bool) {
     let angora_out_dir = if sync_afl {
         gen_path_afl(out_dir)
     } else {
@@ -134,6 +239,10 @@ fn initialize_directories(in_dir: &str, out_dir: &str, sync_afl: bool) -> (PathB
     }
 
     let out_dir = &angora_out_dir;
+    if restart && out_dir.join(defs::CHECKPOINT_FILE).exists() {
+        warn!("Resume from the checkpoint in {:?}.", out_dir);
+        return (out_dir.join(defs::INPUTS_DIR), angora_out_dir, true);
+    }
     let seeds_dir = if restart {
         let orig_out_dir = out_dir.with_extension(Local::now().to_rfc3339());
         fs::rename(&out_dir, orig_out_dir.clone()).unwrap();
@@ -143,7 +252,45 @@ fn initialize_directories(in_dir: &str, out_dir: &str, sync_afl: bool) -> (PathB
         PathBuf::from(in_dir)
     };
 
-    (seeds_dir, angora_out_dir)
+    (seeds_dir, angora_out_dir, false)
+}
+
+fn load_checkpoint(angora_out_dir: &Path) -> Checkpoint {
+    let path = angora_out_dir.join(defs::CHECKPOINT_FILE);
+    let checkpoint = fs::read(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|buf| serde_json::from_slice(&buf).map_err(|e| e.to_string()));
+    match checkpoint {
+        Ok(checkpoint) => checkpoint,
+        Err(e) => {
+            error!("Could not load {:?}: {}", path, e);
+            error!("Remove it to resume by rerunning the queue instead.");
+            panic!();
+        },
+    }
+}
+
+// Written to a temporary file first, so a kill in the middle leaves the last one intact.
+fn save_checkpoint(depot: &depot::Depot, stats: &RwLock<stats::ChartStats>, time_limit: u64) {
+    let checkpoint = Checkpoint {
+        depot: depot.checkpoint(),
+        stats: stats.read().unwrap().checkpoint(),
+        time_limit,
+    };
+    let path = depot
+        .dirs
+        .inputs_dir
+        .parent()
+        .unwrap()
+        .join(defs::CHECKPOINT_FILE);
+    let tmp_path = path.with_extension("tmp");
+    let res = serde_json::to_vec(&checkpoint)
+        .map_err(io::Error::from)
+        .and_then(|buf| fs::write(&tmp_path, buf))
+        .and_then(|_| fs::rename(&tmp_path, &path));
+    if let Err(e) = res {
+        warn!("Could not save {:?}: {:?}", path, e);
+    }
 }
 
 fn gen_path_afl(out_dir: &str) -> PathBuf {
@@ -178,6 +325,31 @@ fn create_stats_file_and_write_pid(angora_out_dir: &PathBuf) -> PathBuf {
     fuzzer_stats
 }
 
+fn save_lcov(depot: &depot::Depot, global_branches: &branches::GlobalBranches) {
+    let source_map = match source_map::get() {
+        Some(source_map) => source_map,
+        None => return,
+    };
+    let path = depot
+        .dirs
+        .inputs_dir
+        .parent()
+        .unwrap()
+        .join(defs::COVERAGE_LCOV_FILE);
+    let res = fs::File::create(&path).and_then(|f| {
+        let mut w = io::BufWriter::new(f);
+        source_map.write_lcov(
+            &mut w,
+            |i| global_branches.is_covered(i),
+            &depot.cond_sides(),
+        )?;
+        w.flush()
+    });
+    if let Err(e) = res {
+        warn!("Could not save {:?}: {:?}", path, e);
+    }
+}
+
 fn init_cpus_and_run_fuzzing_threads(
     bind: Option<usize>,
     num_jobs: usize,
@@ -216,7 +388,11 @@ fn init_cpus_and_run_fuzzing_threads(
             if bind_cpus {
                 bind_cpu::bind_thread_to_cpu_core(cid);
             }
-            fuzz_loop::fuzz_loop(r, cmd, d, b, s);
+            if cmd.libfuzzer.is_some() {
+                fuzz_loop::fuzz_loop::<executor::LibFuzzerExecutor>(r, cmd, d, b, s);
+            } else {
+                fuzz_loop::fuzz_loop::<executor::ForkExecutor>(r, cmd, d, b, s);
+            }
         });
         handlers.push(handler);
     }
@@ -228,7 +404,7 @@ fn main_thread_sync_and_log(
     out_dir: &str,
     sync_afl: bool,
     running: Arc<AtomicBool>,
-    executor: &mut executor::Executor,
+    executor: &mut dyn Executor,
     depot: &Arc<depot::Depot>,
     global_branches: &Arc<branches::GlobalBranches>,
     stats: &Arc<RwLock<stats::ChartStats>>,
@@ -241,6 +417,7 @@ fn main_thread_sync_and_log(
         depot::sync_afl(executor, running.clone(), sync_dir, &mut synced_ids);
     }
     let mut sync_counter = 1;
+    let mut save_counter = 12;
     show_stats(&mut log_file, depot, global_branches, stats);
     while running.load(Ordering::SeqCst) {
         if cfg!(debug_assertions) {
@@ -254,6 +431,15 @@ fn main_thread_sync_and_log(
             sync_counter = 12;
         }
 
+        save_counter -= 1;
+        if save_counter <= 0 {
+            global_branches.save(&depot.dirs.branches_dir);
+            save_lcov(depot, global_branches);
+            save_checkpoint(depot, stats, executor.time_limit());
+            depot.save_crash_index();
+            save_counter = 12;
+        }
+
         show_stats(&mut log_file, depot, global_branches, stats);
         if Arc::strong_count(&child_count) == 1 {
             let s = stats.read().unwrap();
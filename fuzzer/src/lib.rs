@@ -7,10 +7,14 @@ extern crate derive_more;
 
 pub mod branches;
 pub mod cond_stmt;
+mod crash_class;
+mod crash_report;
 mod depot;
 pub mod executor;
+pub mod minimize;
 mod mut_input;
 mod search;
+pub mod source_map;
 mod stats;
 pub mod track;
 
@@ -23,4 +27,4 @@ mod check_dep;
 mod command;
 mod tmpfs;
 
-pub use crate::fuzz_main::fuzz_main;
+pub use crate::{command::parse_time_limit, fuzz_main::fuzz_main};
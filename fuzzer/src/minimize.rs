@@ -0,0 +1,169 @@
+// Shrink an input while it keeps doing what makes it interesting,
+// e.g. hanging, by removing blocks of it, and make it simpler to read
+// by replacing its bytes with '0', as afl-tmin does.
+
+const NORMAL_BYTE: u8 = b'0';
+// Number of blocks that the input is split into for normalization.
+const NORMALIZE_STEPS: usize = 16;
+
+// Calls `keeps` until `max_execs` candidates have been tried, and rejects the others.
+struct Limited<F> {
+    keeps: F,
+    execs: usize,
+    max_execs: usize,
+}
+
+impl<F: FnMut(&Vec<u8>) -> bool> Limited<F> {
+    fn new(keeps: F, max_execs: usize) -> Self {
+        Self {
+            keeps,
+            execs: 0,
+            max_execs,
+        }
+    }
+
+    fn keeps(&mut self, candidate: &Vec<u8>) -> bool {
+        if self.exhausted() {
+            return false;
+        }
+        self.execs += 1;
+        (self.keeps)(candidate)
+    }
+
+    fn exhausted(&self) -> bool {
+        self.execs >= self.max_execs
+    }
+}
+
+/// Remove blocks of `buf`, from half of it down to single bytes, as long as
+/// `keeps` holds for what is left. Stops after `max_execs` calls of `keeps`.
+pub fn minimize<F: FnMut(&Vec<u8>) -> bool>(buf: &Vec<u8>, max_execs: usize, keeps: F) -> Vec<u8> {
+    remove_blocks(buf.clone(), &mut Limited::new(keeps, max_execs))
+}
+
+/// Like afl-tmin: normalize blocks, remove blocks, then normalize each byte value
+/// and each byte, and again until nothing changes. Stops after `max_execs` calls of `keeps`.
+pub fn tmin<F: FnMut(&Vec<u8>) -> bool>(buf: &Vec<u8>, max_execs: usize, keeps: F) -> Vec<u8> {
+    let mut limited = Limited::new(keeps, max_execs);
+    let mut buf = buf.clone();
+    loop {
+        let last = buf.clone();
+        buf = normalize_blocks(buf, &mut limited);
+        buf = remove_blocks(buf, &mut limited);
+        buf = normalize_values(buf, &mut limited);
+        buf = normalize_bytes(buf, &mut limited);
+        if buf == last || limited.exhausted() {
+            return buf;
+        }
+    }
+}
+
+fn remove_blocks<F: FnMut(&Vec<u8>) -> bool>(
+    mut buf: Vec<u8>,
+    limited: &mut Limited<F>,
+) -> Vec<u8> {
+    let mut block = (buf.len() / 2).max(1);
+    loop {
+        let mut pos = 0;
+        while pos < buf.len() {
+            if limited.exhausted() {
+                return buf;
+            }
+            let mut candidate = buf.clone();
+            candidate.drain(pos..(pos + block).min(buf.len()));
+            if limited.keeps(&candidate) {
+                buf = candidate;
+            } else {
+                pos += block;
+            }
+        }
+        if block == 1 {
+            return buf;
+        }
+        block /= 2;
+    }
+}
+
+fn normalize_blocks<F: FnMut(&Vec<u8>) -> bool>(
+    mut buf: Vec<u8>,
+    limited: &mut Limited<F>,
+) -> Vec<u8> {
+    let block = (buf.len() / NORMALIZE_STEPS).max(1);
+    let mut pos = 0;
+    while pos < buf.len() {
+        let end = (pos + block).min(buf.len());
+        if buf[pos..end].iter().any(|&b| b != NORMAL_BYTE) {
+            let mut candidate = buf.clone();
+            candidate[pos..end]
+                .iter_mut()
+                .for_each(|b| *b = NORMAL_BYTE);
+            if limited.keeps(&candidate) {
+                buf = candidate;
+            }
+        }
+        pos = end;
+    }
+    buf
+}
+
+// Replace every occurrence of a byte value at once.
+fn normalize_values<F: FnMut(&Vec<u8>) -> bool>(
+    mut buf: Vec<u8>,
+    limited: &mut Limited<F>,
+) -> Vec<u8> {
+    for v in 0..=255 {
+        if v == NORMAL_BYTE || !buf.contains(&v) {
+            continue;
+        }
+        let candidate = buf
+            .iter()
+            .map(|&b| if b == v { NORMAL_BYTE } else { b })
+            .collect();
+        if limited.keeps(&candidate) {
+            buf = candidate;
+        }
+    }
+    buf
+}
+
+fn normalize_bytes<F: FnMut(&Vec<u8>) -> bool>(
+    mut buf: Vec<u8>,
+    limited: &mut Limited<F>,
+) -> Vec<u8> {
+    for i in 0..buf.len() {
+        if buf[i] == NORMAL_BYTE {
+            continue;
+        }
+        let mut candidate = buf.clone();
+        candidate[i] = NORMAL_BYTE;
+        if limited.keeps(&candidate) {
+            buf = candidate;
+        }
+    }
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minimize() {
+        let buf = b"aaaaXbbbbYcc".to_vec();
+        let keeps = |buf: &Vec<u8>| buf.contains(&b'X') && buf.contains(&b'Y');
+        assert_eq!(minimize(&buf, 1000, keeps), b"XY".to_vec());
+        assert_eq!(minimize(&buf, 0, keeps), buf);
+        assert!(minimize(&Vec::new(), 10, |_| true).is_empty());
+    }
+
+    #[test]
+    fn test_tmin() {
+        // Needs 4 bytes, the first one an 'X'.
+        let keeps = |buf: &Vec<u8>| buf.len() >= 4 && buf[0] == b'X';
+        assert_eq!(tmin(&b"Xabcdefgh".to_vec(), 1000, keeps), b"X000".to_vec());
+        assert_eq!(
+            tmin(&b"Xabcdefgh".to_vec(), 0, keeps),
+            b"Xabcdefgh".to_vec()
+        );
+    }
+}
@@ -17,7 +17,7 @@ impl<'a> AFLFuzz<'a> {
     pub fn new(handler: SearchHandler<'a>) -> Self {
         // FIXME:
         let edge_num = handler.cond.base.arg1 as usize;
-        let avg_edge_num = handler.executor.local_stats.avg_edge_num.get() as usize;
+        let avg_edge_num = handler.executor.local_stats().avg_edge_num.get() as usize;
         let run_ratio = if edge_num * 3 < avg_edge_num {
             2
         } else if edge_num < avg_edge_num {
@@ -22,7 +22,7 @@ impl<'a> GdSearch<'a> {
 
     fn execute(&mut self, input: &MutInput) -> u64 {
         if self.handler.skip {
-            return self.handler.executor.last_f as u64;
+            return self.handler.executor.last_f() as u64;
         }
         debug!("input : {:?}", input);
         let f = self.handler.execute_cond(input);
@@ -109,7 +109,7 @@ impl<'a> GdSearch<'a> {
             ep_i += 1;
         }
 
-        if (self.handler.executor.last_f as u64) < std::u64::MAX {
+        if (self.handler.executor.last_f() as u64) < std::u64::MAX {
             self.handler.cond.variables = input.get_value();
         }
     }
@@ -5,7 +5,7 @@ use angora_common::debug_cmpid;
 /// Execution monitor
 pub struct SearchHandler<'a> {
     running: Arc<AtomicBool>,
-    pub executor: &'a mut Executor,
+    pub executor: &'a mut dyn Executor,
     pub cond: &'a mut CondStmt,
     pub buf: Vec<u8>,
     pub max_times: Counter,
@@ -15,11 +15,11 @@ pub struct SearchHandler<'a> {
 impl<'a> SearchHandler<'a> {
     pub fn new(
         running: Arc<AtomicBool>,
-        executor: &'a mut Executor,
+        executor: &'a mut dyn Executor,
         cond: &'a mut CondStmt,
         buf: Vec<u8>,
     ) -> Self {
-        executor.local_stats.register(cond);
+        executor.local_stats().register(cond);
         cond.state_times = cond.state_times + 1;
         cond.fuzz_times = cond.fuzz_times + 1;
         Self {
@@ -45,14 +45,14 @@ impl<'a> SearchHandler<'a> {
         }
 
         // bonus
-        if self.executor.has_new_path {
+        if self.executor.has_new_path() {
             self.max_times += config::BONUS_EXEC_NUM.into();
         }
 
         // Skip if it reach max epoch,
         // Like a Round-Robin algorithm,
         // To avoid stuck in some cond too much time.
-        if self.executor.local_stats.num_exec > self.max_times {
+        if self.executor.local_stats().num_exec > self.max_times {
             self.skip = true;
         }
     }
@@ -137,7 +137,7 @@ impl<'a> IntGdSearch<'a> {
     // TODO: What's the sense of this execute.
     fn execute(&mut self, input: &MutInput) -> i128 {
         if self.handler.skip {
-            return self.handler.executor.last_f;
+            return self.handler.executor.last_f();
         }
         debug_cmpid!(self.cmpid, "input: {:?}", input);
         let f = self.handler.execute_cond(input);
@@ -0,0 +1,249 @@
+// Source locations of the cmpids and the branch table slots, which the LLVM pass
+// writes to ANGORA_SOURCE_MAP when it compiles the fast binary.
+use once_cell::sync::OnceCell;
+use serde_derive::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, fs,
+    io::{self, prelude::*},
+    path::Path,
+};
+
+static SOURCE_MAP: OnceCell<SourceMap> = OnceCell::new();
+
+// The runtime sets the bits above the id for exploitation conditions.
+const CMPID_MASK: u32 = 0x1fff_ffff;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SourceLoc {
+    pub file: String,
+    // 0 if the instruction has no debug info.
+    pub line: u32,
+    pub col: u32,
+}
+
+impl fmt::Display for SourceLoc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.col)
+    }
+}
+
+#[derive(Deserialize)]
+struct CondLoc {
+    cmpid: u32,
+    #[serde(flatten)]
+    loc: SourceLoc,
+}
+
+#[derive(Deserialize)]
+struct EdgeLoc {
+    // Of the branch the edge leaves.
+    #[serde(flatten)]
+    loc: SourceLoc,
+    block: u32,
+    side: u32,
+}
+
+#[derive(Deserialize)]
+struct FunctionLocs {
+    edges: Vec<EdgeLoc>,
+}
+
+// Slot `base + i` counts edge `i` of `function`.
+#[derive(Deserialize)]
+struct Region {
+    base: usize,
+    function: usize,
+}
+
+#[derive(Deserialize)]
+struct SourceMapFile {
+    conds: Vec<CondLoc>,
+    functions: Vec<FunctionLocs>,
+    regions: Vec<Region>,
+}
+
+pub struct SourceMap {
+    conds: HashMap<u32, SourceLoc>,
+    functions: Vec<FunctionLocs>,
+    // The function and edge of each slot.
+    slots: HashMap<usize, (usize, usize)>,
+}
+
+#[derive(Default)]
+struct FileCoverage {
+    // (line, block, side) of a branch, and how many times it is taken.
+    branches: BTreeMap<(u32, u32, u32), usize>,
+    lines: BTreeMap<u32, usize>,
+}
+
+impl SourceMap {
+    fn new(file: SourceMapFile) -> Self {
+        let conds = file
+            .conds
+            .into_iter()
+            .map(|cond| (cond.cmpid, cond.loc))
+            .collect();
+        let mut slots = HashMap::new();
+        for region in &file.regions {
+            if let Some(function) = file.functions.get(region.function) {
+                for edge in 0..function.edges.len() {
+                    slots.insert(region.base + edge, (region.function, edge));
+                }
+            }
+        }
+        Self {
+            conds,
+            functions: file.functions,
+            slots,
+        }
+    }
+
+    fn load(path: &Path) -> io::Result<Self> {
+        let file = serde_json::from_slice(&fs::read(path)?)?;
+        Ok(Self::new(file))
+    }
+
+    pub fn cond_loc(&self, cmpid: u32) -> Option<&SourceLoc> {
+        self.conds.get(&(cmpid & CMPID_MASK))
+    }
+
+    /// Write the branches, and both sides of the conditions, that the corpus has
+    /// taken as lcov tracefile records. `is_covered` tells if a slot is hit, and
+    /// `cond_sides` has a bit set for each side of a cmpid reached.
+    pub fn write_lcov<W: Write, F: Fn(usize) -> bool>(
+        &self,
+        w: &mut W,
+        is_covered: F,
+        cond_sides: &HashMap<u32, u32>,
+    ) -> io::Result<()> {
+        let mut files: BTreeMap<&str, FileCoverage> = BTreeMap::new();
+        let mut taken = vec![];
+        for function in &self.functions {
+            taken.push(vec![0; function.edges.len()]);
+        }
+        for (&slot, &(function, edge)) in &self.slots {
+            if is_covered(slot) {
+                taken[function][edge] += 1;
+            }
+        }
+        for (function, taken) in self.functions.iter().zip(&taken) {
+            for (edge, &n) in function.edges.iter().zip(taken) {
+                if edge.loc.line == 0 {
+                    continue;
+                }
+                let file = files.entry(&edge.loc.file).or_default();
+                file.branches
+                    .insert((edge.loc.line, edge.block, edge.side), n);
+                *file.lines.entry(edge.loc.line).or_insert(0) += n;
+            }
+        }
+        for (&cmpid, loc) in &self.conds {
+            if loc.line == 0 {
+                continue;
+            }
+            let sides = cond_sides.get(&cmpid).copied().unwrap_or(0);
+            let file = files.entry(&loc.file).or_default();
+            let line = file.lines.entry(loc.line).or_insert(0);
+            for side in 0..2 {
+                let n = (sides >> side) as usize & 1;
+                *line += n;
+                // Tell them from the edges, whose blocks are small numbers.
+                file.branches.insert((loc.line, cmpid, side), n);
+            }
+        }
+
+        for (name, file) in &files {
+            writeln!(w, "TN:")?;
+            writeln!(w, "SF:{}", name)?;
+            for (&(line, block, side), &n) in &file.branches {
+                if n > 0 {
+                    writeln!(w, "BRDA:{},{},{},{}", line, block, side, n)?;
+                } else {
+                    writeln!(w, "BRDA:{},{},{},-", line, block, side)?;
+                }
+            }
+            let branches_hit = file.branches.values().filter(|&&n| n > 0).count();
+            writeln!(w, "BRF:{}", file.branches.len())?;
+            writeln!(w, "BRH:{}", branches_hit)?;
+            for (line, n) in &file.lines {
+                writeln!(w, "DA:{},{}", line, n)?;
+            }
+            let lines_hit = file.lines.values().filter(|&&n| n > 0).count();
+            writeln!(w, "LF:{}", file.lines.len())?;
+            writeln!(w, "LH:{}", lines_hit)?;
+            writeln!(w, "end_of_record")?;
+        }
+        Ok(())
+    }
+}
+
+/// Load the source map for the other functions here to use.
+pub fn init(path: &Path) {
+    match SourceMap::load(path) {
+        Ok(source_map) => {
+            info!(
+                "Source map: {} conditions, {} branch slots",
+                source_map.conds.len(),
+                source_map.slots.len()
+            );
+            let _ = SOURCE_MAP.set(source_map);
+        },
+        Err(e) => warn!("Could not load the source map {:?}: {:?}", path, e),
+    }
+}
+
+pub fn get() -> Option<&'static SourceMap> {
+    SOURCE_MAP.get()
+}
+
+pub fn cmpid_loc(cmpid: u32) -> Option<&'static SourceLoc> {
+    get().and_then(|source_map| source_map.cond_loc(cmpid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_lcov() {
+        let file: SourceMapFile = serde_json::from_str(
+            r#"{
+  "conds": [{"cmpid": 1234, "file": "/src/a.c", "line": 12, "col": 7}],
+  "functions": [{"name": "parse", "edges": [
+    {"file": "/src/a.c", "line": 12, "col": 3, "block": 1, "side": 0},
+    {"file": "/src/a.c", "line": 12, "col": 3, "block": 1, "side": 1}]}],
+  "regions": [{"base": 4, "function": 0}, {"base": 8, "function": 0}]
+}"#,
+        )
+        .unwrap();
+        let source_map = SourceMap::new(file);
+        assert_eq!(
+            source_map.cond_loc(1234 | (1 << 29)).unwrap().to_string(),
+            "/src/a.c:12:7"
+        );
+
+        let mut cond_sides = HashMap::new();
+        cond_sides.insert(1234, 0b10);
+        let mut lcov = vec![];
+        source_map
+            .write_lcov(&mut lcov, |slot| slot == 4 || slot == 8, &cond_sides)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(lcov).unwrap(),
+            "TN:
+SF:/src/a.c
+BRDA:12,1,0,2
+BRDA:12,1,1,-
+BRDA:12,1234,0,-
+BRDA:12,1234,1,1
+BRF:4
+BRH:2
+DA:12,3
+LF:1
+LH:1
+end_of_record
+"
+        );
+    }
+}
@@ -1,14 +1,19 @@
 use super::*;
-use crate::{branches::GlobalBranches, depot::Depot};
+use crate::{
+    branches::GlobalBranches,
+    crash_class::{BugClass, Severity},
+    depot::Depot,
+};
 use colored::*;
-use serde_derive::Serialize;
-use std::sync::Arc;
+use serde_derive::{Deserialize, Serialize};
+use std::{collections::BTreeMap, sync::Arc};
 
 #[derive(Default, Serialize)]
 pub struct ChartStats {
     init_time: TimeIns,
     track_time: TimeDuration,
     density: Average,
+    stability: Average,
 
     num_rounds: Counter,
     max_rounds: Counter,
@@ -23,17 +28,70 @@ pub struct ChartStats {
     num_inputs: Counter,
     num_hangs: Counter,
     num_crashes: Counter,
+    num_ooms: Counter,
+    // Crash buckets by their estimated severity and by their bug class.
+    crash_severities: BTreeMap<Severity, usize>,
+    crash_classes: BTreeMap<BugClass, usize>,
 
     fuzz: FuzzStats,
     search: SearchStats,
     state: StateStats,
 }
 
+/// The totals of a campaign that go on when it is resumed. The others are
+/// computed again from the queue and the branches.
+#[derive(Serialize, Deserialize)]
+pub struct StatsCheckpoint {
+    run_time: time::Duration,
+    track_time: TimeDuration,
+    num_rounds: Counter,
+    num_exec: Counter,
+    avg_exec_time: Average,
+    avg_edge_num: Average,
+    num_inputs: Counter,
+    num_hangs: Counter,
+    num_crashes: Counter,
+    num_ooms: Counter,
+    fuzz: FuzzStats,
+}
+
 impl ChartStats {
     pub fn new() -> Self {
         Default::default()
     }
 
+    pub fn checkpoint(&self) -> StatsCheckpoint {
+        StatsCheckpoint {
+            run_time: self.init_time.0.elapsed(),
+            track_time: self.track_time,
+            num_rounds: self.num_rounds,
+            num_exec: self.num_exec,
+            avg_exec_time: self.avg_exec_time,
+            avg_edge_num: self.avg_edge_num,
+            num_inputs: self.num_inputs,
+            num_hangs: self.num_hangs,
+            num_crashes: self.num_crashes,
+            num_ooms: self.num_ooms,
+            fuzz: self.fuzz.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, checkpoint: StatsCheckpoint) {
+        // Count the run time from before the resume too.
+        let now = time::Instant::now();
+        self.init_time = TimeIns(now.checked_sub(checkpoint.run_time).unwrap_or(now));
+        self.track_time = checkpoint.track_time;
+        self.num_rounds = checkpoint.num_rounds;
+        self.num_exec = checkpoint.num_exec;
+        self.avg_exec_time = checkpoint.avg_exec_time;
+        self.avg_edge_num = checkpoint.avg_edge_num;
+        self.num_inputs = checkpoint.num_inputs;
+        self.num_hangs = checkpoint.num_hangs;
+        self.num_crashes = checkpoint.num_crashes;
+        self.num_ooms = checkpoint.num_ooms;
+        self.fuzz = checkpoint.fuzz;
+    }
+
     pub fn min_rounds(&self) -> usize {
         self.min_rounds.0
     }
@@ -58,6 +116,7 @@ impl ChartStats {
         self.num_hangs += local.num_hangs;
         st.num_crashes += local.num_crashes;
         self.num_crashes += local.num_crashes;
+        self.num_ooms += local.num_ooms;
 
         local.clear();
     }
@@ -66,6 +125,9 @@ impl ChartStats {
         self.get_speed();
         self.iter_pq(depot);
         self.sync_from_branches(gb);
+        let (severities, classes) = depot.crash_classes();
+        self.crash_severities = severities;
+        self.crash_classes = classes;
     }
 
     fn iter_pq(&mut self, depot: &Arc<Depot>) {
@@ -107,6 +169,7 @@ impl ChartStats {
 
     fn sync_from_branches(&mut self, gb: &Arc<GlobalBranches>) {
         self.density = Average::new(gb.get_density(), 0);
+        self.stability = Average::new(gb.get_stability(), 0);
     }
 
     fn get_speed(&mut self) {
@@ -133,6 +196,15 @@ impl ChartStats {
         )
     }
 
+    fn format_crash_classes(&self) -> String {
+        let classes: Vec<String> = self
+            .crash_classes
+            .iter()
+            .map(|(bug, n)| format!("{}: {}", bug, n))
+            .collect();
+        classes.join(", ")
+    }
+
     pub fn get_explore_num(&self) -> usize {
         self.fuzz
             .get(fuzz_type::FuzzType::ExploreFuzz.index())
@@ -162,10 +234,11 @@ impl fmt::Display for ChartStats {
 {}
 {}
     TIMING |     RUN: {},   TRACK: {}
-  COVERAGE |    EDGE: {},   DENSITY: {}%
+  COVERAGE |    EDGE: {},   DENSITY: {}%,   STABILITY: {}%
     EXECS  |   TOTAL: {},     ROUND: {}  MIN/MAX_R: {}/{}  MIN_P: {}
     SPEED  |  PERIOD: {:6}r/s    TIME: {}us, 
-    FOUND  |  NORMAL: {},     HANGS: {},   CRASHES: {}
+    FOUND  |  NORMAL: {},     HANGS: {},   CRASHES: {},   OOMS: {}
+   BUCKETS |    HIGH: {},    MEDIUM: {},      LOW: {}   {}
 {}
 {}
 {}
@@ -180,6 +253,7 @@ impl fmt::Display for ChartStats {
             self.track_time,
             self.avg_edge_num,
             self.density,
+            self.stability,
             self.num_exec,
             self.num_rounds,
             self.min_rounds,
@@ -190,6 +264,11 @@ impl fmt::Display for ChartStats {
             self.num_inputs,
             self.num_hangs,
             self.num_crashes,
+            self.num_ooms,
+            self.crash_severities.get(&Severity::High).unwrap_or(&0),
+            self.crash_severities.get(&Severity::Medium).unwrap_or(&0),
+            self.crash_severities.get(&Severity::Low).unwrap_or(&0),
+            self.format_crash_classes(),
             " -- FUZZ -- ".blue().bold(),
             self.fuzz,
             " -- SEARCH -- ".blue().bold(),
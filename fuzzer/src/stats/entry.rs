@@ -1,6 +1,6 @@
 use super::*;
 use colored::*;
-use serde_derive::Serialize;
+use serde_derive::{Deserialize, Serialize};
 #[derive(
     Default,
     Clone,
@@ -15,6 +15,7 @@ use serde_derive::Serialize;
     Ord,
     PartialOrd,
     Serialize,
+    Deserialize,
     Debug,
 )]
 pub struct Counter(pub usize);
@@ -31,7 +32,7 @@ impl fmt::Display for Counter {
     }
 }
 
-#[derive(Clone, Copy, Constructor, Serialize)]
+#[derive(Clone, Copy, Constructor, Serialize, Deserialize)]
 pub struct Average(pub f32, usize);
 
 impl Default for Average {
@@ -114,7 +115,7 @@ impl SyncAverage {
     }
 }
 
-#[derive(Default, Clone, Copy, Add, AddAssign, From, Into, Serialize)]
+#[derive(Default, Clone, Copy, Add, AddAssign, From, Into, Serialize, Deserialize)]
 pub struct TimeDuration(pub time::Duration);
 
 impl fmt::Display for TimeDuration {
@@ -1,8 +1,8 @@
 use super::*;
 use crate::cond_stmt::CondStmt;
-use serde_derive::Serialize;
+use serde_derive::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Default, Serialize)]
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
 pub struct StrategyStats {
     pub time: TimeDuration,
     pub num_conds: usize,
@@ -29,7 +29,7 @@ impl fmt::Display for StrategyStats {
     }
 }
 
-#[derive(Clone, Default, Serialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct FuzzStats([StrategyStats; fuzz_type::FUZZ_TYPE_NUM]);
 
 impl FuzzStats {
@@ -182,6 +182,11 @@ static void add_angora_runtime() {
   if (clang_type == CLANG_FAST_TYPE) {
     cc_params[cc_par_cnt++] =
         alloc_printf("%s/lib/libruntime_fast.a", obj_path);
+    // Shared objects have no main, so libfuzzer_loader calls these itself.
+    cc_params[cc_par_cnt++] = (const char *)"-Wl,-u,__trace_init";
+    cc_params[cc_par_cnt++] = (const char *)"-Wl,-u,__angora_persistent_loop";
+    cc_params[cc_par_cnt++] = (const char *)"-Wl,-u,__angora_fuzz_testcase_buf";
+    cc_params[cc_par_cnt++] = (const char *)"-Wl,-u,__angora_fuzz_testcase_len";
   } else if (clang_type == CLANG_TRACK_TYPE || clang_type == CLANG_DFSAN_TYPE) {
     cc_params[cc_par_cnt++] = (const char *)"-Wl,--whole-archive";
     cc_params[cc_par_cnt++] =
@@ -402,19 +407,40 @@ static void edit_params(u32 argc, const char **argv) {
 
    */
 
-  /*
-  cc_params[cc_par_cnt++] = (u8*)"-D__ANGORA_LOOP(_A)="
-    "({ static volatile char *_B __attribute__((used)); "
-    " _B = (char*)\"" PERSIST_SIG "\"; "
+  if (clang_type == CLANG_FAST_TYPE) {
+    cc_params[cc_par_cnt++] = (const char *)"-D__ANGORA_LOOP(_A)="
+      "({ static volatile char *_B __attribute__((used)); "
+      " _B = (char*)\"" PERSIST_SIG "\"; "
 #ifdef __APPLE__
-    "__attribute__((visibility(\"default\"))) "
-    "int _L(unsigned int) __asm__(\"___angora_persistent_loop\"); "
+      "__attribute__((visibility(\"default\"))) "
+      "int _L(unsigned int) __asm__(\"___angora_persistent_loop\"); "
 #else
-    "__attribute__((visibility(\"default\"))) "
-    "int _L(unsigned int) __asm__(\"__angora_persistent_loop\"); "
+      "__attribute__((visibility(\"default\"))) "
+      "int _L(unsigned int) __asm__(\"__angora_persistent_loop\"); "
 #endif
-    "_L(_A); })";
+      "_L(_A); })";
+
+    cc_params[cc_par_cnt++] = (const char *)"-D__ANGORA_FUZZ_TESTCASE_BUF="
+      "({ static volatile char *_B __attribute__((used)); "
+      " _B = (char*)\"" SHM_INPUT_SIG "\"; "
+      "__attribute__((visibility(\"default\"))) "
+      "unsigned char *_F(void) __asm__(\"__angora_fuzz_testcase_buf\"); "
+      "_F(); })";
+    cc_params[cc_par_cnt++] = (const char *)"-D__ANGORA_FUZZ_TESTCASE_LEN="
+      "({ __attribute__((visibility(\"default\"))) "
+      "long _N(void) __asm__(\"__angora_fuzz_testcase_len\"); "
+      "_N(); })";
+  } else {
+    // Track and sanitizer builds run the loop body exactly once.
+    cc_params[cc_par_cnt++] = (const char *)"-D__ANGORA_LOOP(_A)="
+      "({ static int _C = 1; _C-- > 0; })";
+    // and always read the input file, where taints come from.
+    cc_params[cc_par_cnt++] =
+        (const char *)"-D__ANGORA_FUZZ_TESTCASE_BUF=((unsigned char *)0)";
+    cc_params[cc_par_cnt++] = (const char *)"-D__ANGORA_FUZZ_TESTCASE_LEN=(-1L)";
+  }
 
+  /*
   cc_params[cc_par_cnt++] = (u8*)"-D__ANGORA_INIT()="
     "do { static volatile char *_A __attribute__((used)); "
     " _A = (char*)\"" DEFER_SIG "\"; "
@@ -4,6 +4,7 @@
   to write custom functions, modify custom/angora_abilist.txt first
  */
 
+#define _GNU_SOURCE // accept4
 #include <assert.h>
 #include <fcntl.h>
 #include <stdarg.h>
@@ -13,6 +14,7 @@
 #include <stdlib.h>
 #include <string.h>
 #include <sys/mman.h>
+#include <sys/socket.h>
 #include <sys/stat.h>
 #include <time.h>
 #include <unistd.h>
@@ -233,12 +235,30 @@ size_t __dfsw_fread_unlocked(void *buf, size_t size, size_t count, FILE *fd,
   return ret;
 }
 
+// Datagram sockets are never accepted, so they become inputs on first read.
+static int is_fuzzing_sock(int fd) {
+  if (is_fuzzing_fd(fd)) return 1;
+  int type = 0;
+  socklen_t len = sizeof(type);
+  if (getsockopt(fd, SOL_SOCKET, SO_TYPE, &type, &len) == 0 &&
+      type == SOCK_DGRAM) {
+    __angora_io_add_sock_fd(fd);
+    return 1;
+  }
+  return 0;
+}
+
 DEFAULT_VISIBILITY
 ssize_t __dfsw_read(int fd, void *buf, size_t count, dfsan_label fd_label,
                     dfsan_label buf_label, dfsan_label count_label,
                     dfsan_label *ret_label) {
   long offset = lseek(fd, 0, SEEK_CUR);
   ssize_t ret = read(fd, buf, count);
+  if (offset < 0 && is_fuzzing_sock(fd)) {
+    // sockets can not seek
+    long sock_offset = __angora_io_sock_offset(fd, ret);
+    if (sock_offset >= 0) offset = sock_offset;
+  }
 #ifdef DEBUG_INFO
   fprintf(stderr, "### read %d, range is %ld, %ld/%ld \n", fd, offset, ret,
           count);
@@ -252,6 +272,78 @@ ssize_t __dfsw_read(int fd, void *buf, size_t count, dfsan_label fd_label,
   return ret;
 }
 
+// Every accepted connection carries the input sent by the fuzzer.
+DEFAULT_VISIBILITY
+int __dfsw_accept(int sockfd, struct sockaddr *addr, socklen_t *addrlen,
+                  dfsan_label sockfd_label, dfsan_label addr_label,
+                  dfsan_label addrlen_label, dfsan_label *ret_label) {
+  int fd = accept(sockfd, addr, addrlen);
+#ifdef DEBUG_INFO
+  fprintf(stderr, "### accept, sockfd is %d, fd is %d \n", sockfd, fd);
+#endif
+  if (fd >= 0) {
+    __angora_io_add_sock_fd(fd);
+  }
+  *ret_label = 0;
+  return fd;
+}
+
+DEFAULT_VISIBILITY
+int __dfsw_accept4(int sockfd, struct sockaddr *addr, socklen_t *addrlen,
+                   int flags, dfsan_label sockfd_label, dfsan_label addr_label,
+                   dfsan_label addrlen_label, dfsan_label flags_label,
+                   dfsan_label *ret_label) {
+  int fd = accept4(sockfd, addr, addrlen, flags);
+#ifdef DEBUG_INFO
+  fprintf(stderr, "### accept4, sockfd is %d, fd is %d \n", sockfd, fd);
+#endif
+  if (fd >= 0) {
+    __angora_io_add_sock_fd(fd);
+  }
+  *ret_label = 0;
+  return fd;
+}
+
+static void taint_sock_read(int fd, void *buf, ssize_t ret, size_t len,
+                            int flags, dfsan_label *ret_label) {
+  if (!is_fuzzing_sock(fd)) {
+    *ret_label = 0;
+    return;
+  }
+  // MSG_PEEK leaves the bytes to be read again.
+  long offset = __angora_io_sock_offset(fd, (flags & MSG_PEEK) ? 0 : ret);
+  if (ret > 0) assign_taint_labels_exf(buf, offset, ret, len, 1);
+  *ret_label = __angora_get_sp_label(offset, 1);
+}
+
+DEFAULT_VISIBILITY
+ssize_t __dfsw_recv(int sockfd, void *buf, size_t len, int flags,
+                    dfsan_label sockfd_label, dfsan_label buf_label,
+                    dfsan_label len_label, dfsan_label flags_label,
+                    dfsan_label *ret_label) {
+  ssize_t ret = recv(sockfd, buf, len, flags);
+#ifdef DEBUG_INFO
+  fprintf(stderr, "### recv %d, %ld/%ld \n", sockfd, ret, len);
+#endif
+  taint_sock_read(sockfd, buf, ret, len, flags, ret_label);
+  return ret;
+}
+
+DEFAULT_VISIBILITY
+ssize_t __dfsw_recvfrom(int sockfd, void *buf, size_t len, int flags,
+                        struct sockaddr *src_addr, socklen_t *addrlen,
+                        dfsan_label sockfd_label, dfsan_label buf_label,
+                        dfsan_label len_label, dfsan_label flags_label,
+                        dfsan_label src_addr_label, dfsan_label addrlen_label,
+                        dfsan_label *ret_label) {
+  ssize_t ret = recvfrom(sockfd, buf, len, flags, src_addr, addrlen);
+#ifdef DEBUG_INFO
+  fprintf(stderr, "### recvfrom %d, %ld/%ld \n", sockfd, ret, len);
+#endif
+  taint_sock_read(sockfd, buf, ret, len, flags, ret_label);
+  return ret;
+}
+
 DEFAULT_VISIBILITY
 ssize_t __dfsw_pread(int fd, void *buf, size_t count, off_t offset,
                      dfsan_label fd_label, dfsan_label buf_label,
@@ -0,0 +1,51 @@
+/*
+  main for the track and sanitized builds of a libFuzzer harness.
+
+  Compile it together with the harness, e.g.
+  USE_TRACK=1 angora-clang harness.c libfuzzer_driver.c -o harness.taint
+  It passes the file in argv[1], or stdin, to LLVMFuzzerTestOneInput once.
+  The fast build is a shared object run by libfuzzer_loader instead.
+ */
+
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+int LLVMFuzzerTestOneInput(const uint8_t *data, size_t size);
+__attribute__((weak)) int LLVMFuzzerInitialize(int *argc, char ***argv);
+
+int main(int argc, char **argv) {
+  if (LLVMFuzzerInitialize) LLVMFuzzerInitialize(&argc, &argv);
+
+  FILE *f = argc > 1 ? fopen(argv[1], "rb") : stdin;
+  if (!f) {
+    perror("fopen");
+    return 1;
+  }
+
+  size_t size = 0, cap = 4096;
+  uint8_t *data = malloc(cap);
+  if (!data) {
+    perror("malloc");
+    return 1;
+  }
+  size_t n;
+  while ((n = fread(data + size, 1, cap - size, f)) > 0) {
+    size += n;
+    if (size == cap) {
+      cap *= 2;
+      uint8_t *new_data = realloc(data, cap);
+      if (!new_data) {
+        perror("realloc");
+        free(data);
+        return 1;
+      }
+      data = new_data;
+    }
+  }
+  if (f != stdin) fclose(f);
+
+  LLVMFuzzerTestOneInput(data, size);
+  free(data);
+  return 0;
+}
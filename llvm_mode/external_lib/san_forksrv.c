@@ -0,0 +1,74 @@
+/*
+  Fork server for the sanitized binary.
+
+  Link libAngoraSanRt.a into the sanitized build with
+  -Wl,--whole-archive,libAngoraSanRt.a,--no-whole-archive.
+  The protocol is the same as runtime_fast/src/forkcli.rs,
+  and each child writes its stderr to ANGORA_SAN_STDERR.
+ */
+
+#include <fcntl.h>
+#include <signal.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/socket.h>
+#include <sys/un.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#include "./defs.h"
+
+// Searched by the fuzzer in the sanitized binary.
+__attribute__((used)) static const char san_forksrv_sig[] = SAN_FORKSRV_SIG;
+
+static void redirect_stderr(void) {
+  const char *path = getenv(SAN_STDERR_VAR);
+  if (!path) return;
+  int fd = open(path, O_WRONLY | O_CREAT | O_TRUNC, 0600);
+  if (fd < 0) return;
+  dup2(fd, STDERR_FILENO);
+  close(fd);
+}
+
+__attribute__((constructor)) static void __angora_san_forksrv(void) {
+  const char *socket_path = getenv(FORKSRV_SOCKET_PATH_VAR);
+  if (!socket_path) return;
+
+  struct sockaddr_un addr;
+  memset(&addr, 0, sizeof(addr));
+  addr.sun_family = AF_UNIX;
+  strncpy(addr.sun_path, socket_path, sizeof(addr.sun_path) - 1);
+
+  int sock = socket(AF_UNIX, SOCK_STREAM, 0);
+  if (sock < 0) return;
+  if (connect(sock, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+    close(sock);
+    return;
+  }
+
+  // Exit on a failed write instead of SIGPIPE once the fuzzer is gone.
+  signal(SIGPIPE, SIG_IGN);
+
+  while (1) {
+    int32_t sig;
+    // The fuzzer writes a shorter message, or closes the socket, to stop us.
+    if (read(sock, &sig, 4) != 4) _exit(0);
+
+    int32_t child_pid = fork();
+    if (child_pid < 0) _exit(1);
+    if (!child_pid) {
+      close(sock);
+      signal(SIGPIPE, SIG_DFL);
+      redirect_stderr();
+      return;
+    }
+
+    if (write(sock, &child_pid, 4) != 4) _exit(1);
+
+    int32_t status;
+    if (waitpid(child_pid, &status, 0) < 0) _exit(1);
+
+    if (write(sock, &status, 4) != 4) _exit(1);
+  }
+}
@@ -22,12 +22,18 @@
 #define CUSTOM_FN_CTX "ANGORA_CUSTOM_FN_CONTEXT"
 #define GEN_ID_RANDOM_VAR "ANGORA_GEN_ID_RANDOM"
 #define OUTPUT_COND_LOC_VAR "ANGORA_OUTPUT_COND_LOC"
+#define SOURCE_MAP_VAR "ANGORA_SOURCE_MAP"
 #define TAINT_CUSTOM_RULE_VAR "ANGORA_TAINT_CUSTOM_RULE"
 #define TAINT_RULE_LIST_VAR "ANGORA_TAINT_RULE_LIST"
 #define FUZZING_INPUT_FILE "cur_input"
 #define PERSIST_ENV_VAR "ANGORA_PERSISTENT"
 #define DEFER_ENV_VAR "ANGORA_DEFER_FORKSRV"
+#define FORKSRV_SOCKET_PATH_VAR "ANGORA_FORKSRV_SOCKET_PATH"
+#define SAN_STDERR_VAR "ANGORA_SAN_STDERR"
 #define PERSIST_SIG "##SIG_ANGORA_PERSISTENT##"
+#define SHM_INPUT_SIG "##SIG_ANGORA_SHM_INPUT##"
+#define TRACK_FORKSRV_SIG "##SIG_ANGORA_TRACK_FORKSRV##"
+#define SAN_FORKSRV_SIG "##SIG_ANGORA_SAN_FORKSRV##"
 #define DEFER_SIG "##SIG_ANGORA_DEFER_FORKSRV##"
 
 #define COND_EQ_OP 32
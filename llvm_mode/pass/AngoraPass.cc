@@ -4,6 +4,7 @@
 
 #include <algorithm>
 #include <fstream>
+#include <map>
 #include <unordered_set>
 #include <utility>
 
@@ -21,6 +22,9 @@
 #include "llvm/IR/Module.h"
 #include "llvm/Support/CommandLine.h"
 #include "llvm/Support/Debug.h"
+#include "llvm/Support/FileSystem.h"
+#include "llvm/Support/JSON.h"
+#include "llvm/Support/Path.h"
 #include "llvm/Support/raw_ostream.h"
 #include "llvm/Transforms/IPO/PassManagerBuilder.h"
 #include "llvm/Transforms/Utils/BasicBlockUtils.h"
@@ -75,6 +79,8 @@ class IndFuncType {
   size_t size = 0;
   llvm::GlobalVariable *symbolPtr = nullptr;
   llvm::ConstantInt *symbolLen = nullptr;
+  // Functions of the type and their offsets in the branch table of a call.
+  std::vector<std::pair<llvm::Function *, size_t>> funcs{};
 
   IndFuncType() = default;
 };
@@ -193,6 +199,20 @@ class AngoraLLVMPass : public ModulePass {
   bool output_cond_loc;
   int num_fn_ctx;
 
+  // Source map of the fast binary, see writeSourceMap.
+  struct EdgeLoc {
+    DILocation *Loc;
+    // Index of the terminator among the branching blocks of the function.
+    unsigned Block;
+    unsigned Side;
+  };
+  std::string source_map_file;
+  std::map<u32, DILocation *> CondLocs;
+  // Instrumented edges of each function, in the order of their slots.
+  unordered_map<Function *, vector<EdgeLoc>> FuncEdges;
+  // Where the slots of a function start in the branch table, for each call.
+  vector<std::pair<size_t, Function *>> Regions;
+
   MDNode *ColdCallWeights;
 
   // Types
@@ -296,6 +316,7 @@ class AngoraLLVMPass : public ModulePass {
                             FuncMap &funcMap, size_t branchCount);
   void bootstrapInstrument(Module &module, FuncMap &funcMap,
                            size_t branchCount);
+  void writeSourceMap();
 
   void visitIntExploitation(Instruction *inst);
   void exploitDiv(Instruction *inst);
@@ -336,7 +357,11 @@ u32 AngoraLLVMPass::getInstructionId(Instruction *Inst) {
     if (ValueAsMetadata *valmeta = dyn_cast<ValueAsMetadata>(metaOp)) {
       Value *val = valmeta->getValue();
       if (ConstantInt *ci = dyn_cast<ConstantInt>(val)) {
-        return ci->getZExtValue();
+        u32 id = ci->getZExtValue();
+        if (FastMode && !source_map_file.empty()) {
+          CondLocs[id] = Inst->getDebugLoc().get();
+        }
+        return id;
       }
     }
     WARNF("Asking for instruction with no ID. ");
@@ -513,6 +538,9 @@ void AngoraLLVMPass::initVariables(Module &M) {
 
   gen_id_random = !!getenv(GEN_ID_RANDOM_VAR);
   output_cond_loc = !!getenv(OUTPUT_COND_LOC_VAR);
+  if (char *source_map = getenv(SOURCE_MAP_VAR)) {
+    source_map_file = source_map;
+  }
 
   num_fn_ctx = -1;
   char *custom_fn_ctx = getenv(CUSTOM_FN_CTX);
@@ -677,12 +705,14 @@ size_t AngoraLLVMPass::branchInstrument(Module &module, SizeMap &sizeMap) {
     auto *branchTable = builder.CreateLoad(branchBase);
     setInsNoSan(branchTable);
 
+    unsigned blockIdx = 0;
     for (auto &block : func) {
       auto *terminator = block.getTerminator();
       size_t numSuccessors = terminator->getNumSuccessors();
       if (numSuccessors < 2) {
         continue;
       }
+      ++blockIdx;
 
       auto result = optMap.find(terminator);
       auto optTable = result == optMap.end()
@@ -711,6 +741,10 @@ size_t AngoraLLVMPass::branchInstrument(Module &module, SizeMap &sizeMap) {
         setValueNoSan(branchVal);
         setInsNoSan(builder.CreateStore(branchVal, branchIdx));
 
+        if (!source_map_file.empty()) {
+          FuncEdges[&func].push_back(
+              {terminator->getDebugLoc().get(), blockIdx, (unsigned)i});
+        }
         ++funcCount;
       }
     }
@@ -900,6 +934,8 @@ void AngoraLLVMPass::indirectFuncCallAnalysis(Module &module, SizeMap &sizeMap,
       auto *indPair = ConstantStruct::get(ind_tuple, funcPtr, funcOffset);
 
       typeEntry->second.data.push_back(indPair);
+      typeEntry->second.funcs.push_back(
+          make_pair(&func, typeEntry->second.size));
       typeEntry->second.size += funcSize->second;
 
       break;
@@ -967,6 +1003,7 @@ size_t AngoraLLVMPass::funcCallInstrument(Module &module, SizeMap &sizeMap,
         setValueNoSan(branchIdx);
         setInsNoSan(builder.CreateStore(branchIdx, branchBase));
 
+        Regions.push_back(make_pair(branchCount, &func));
         branchCount += result->second;
       }
     }
@@ -1006,6 +1043,7 @@ size_t AngoraLLVMPass::funcCallInstrument(Module &module, SizeMap &sizeMap,
             setValueNoSan(branchIdx);
             setInsNoSan(builder.CreateStore(branchIdx, branchBase));
 
+            Regions.push_back(make_pair(branchCount, calledFunction));
             branchCount += result->second;
           } else {
             // foreign function call
@@ -1036,6 +1074,10 @@ size_t AngoraLLVMPass::funcCallInstrument(Module &module, SizeMap &sizeMap,
             setValueNoSan(branchIdx);
             setInsNoSan(builder.CreateStore(branchIdx, branchBase));
 
+            for (auto &func : result->second.funcs) {
+              Regions.push_back(
+                  make_pair(branchCount + func.second, func.first));
+            }
             branchCount += result->second.size;
           } else {
             // no type
@@ -1169,6 +1211,92 @@ void AngoraLLVMPass::bootstrapInstrument(Module &module, FuncMap &funcMap,
   }
 }
 
+static std::string getLocFile(DILocation *Loc) {
+  std::string File = Loc->getFilename().str();
+  if (sys::path::is_relative(File) && !Loc->getDirectory().empty()) {
+    SmallString<256> Path(Loc->getDirectory());
+    sys::path::append(Path, File);
+    return Path.str().str();
+  }
+  return File;
+}
+
+static void writeLoc(json::OStream &J, DILocation *Loc) {
+  J.attribute("file", Loc ? getLocFile(Loc) : "");
+  J.attribute("line", Loc ? (int64_t)Loc->getLine() : 0);
+  J.attribute("col", Loc ? (int64_t)Loc->getColumn() : 0);
+}
+
+/*
+ * Write the source locations of the cmpids and of the branch table slots to
+ * ANGORA_SOURCE_MAP, for the fuzzer to annotate its output with:
+ *
+ * ```
+ * {
+ *   "conds": [{"cmpid": 1234, "file": "/src/a.c", "line": 12, "col": 7}],
+ *   "functions": [{"name": "parse", "edges": [
+ *     {"file": "/src/a.c", "line": 12, "col": 3, "block": 1, "side": 0}]}],
+ *   "regions": [{"base": 4, "function": 0}]
+ * }
+ * ```
+ *
+ * Slot `base + i` of the table counts edge `i` of the function of a region.
+ * The first slots are shared by the functions called from uninstrumented
+ * code, so they have no region.
+ */
+void AngoraLLVMPass::writeSourceMap() {
+  std::error_code EC;
+  raw_fd_ostream OS(source_map_file, EC, sys::fs::OF_Text);
+  if (EC) {
+    WARNF("Could not write the source map to %s: %s", source_map_file.c_str(),
+          EC.message().c_str());
+    return;
+  }
+
+  unordered_map<Function *, size_t> FuncIdx;
+  json::OStream J(OS);
+  J.object([&] {
+    J.attributeArray("conds", [&] {
+      for (auto &Cond : CondLocs) {
+        if (!Cond.second) continue;
+        J.object([&] {
+          J.attribute("cmpid", (int64_t)Cond.first);
+          writeLoc(J, Cond.second);
+        });
+      }
+    });
+    J.attributeArray("functions", [&] {
+      for (auto &Func : FuncEdges) {
+        size_t Idx = FuncIdx.size();
+        FuncIdx[Func.first] = Idx;
+        J.object([&] {
+          J.attribute("name", Func.first->getName().str());
+          J.attributeArray("edges", [&] {
+            for (auto &Edge : Func.second) {
+              J.object([&] {
+                writeLoc(J, Edge.Loc);
+                J.attribute("block", (int64_t)Edge.Block);
+                J.attribute("side", (int64_t)Edge.Side);
+              });
+            }
+          });
+        });
+      }
+    });
+    J.attributeArray("regions", [&] {
+      for (auto &Region : Regions) {
+        auto Func = FuncIdx.find(Region.second);
+        if (Func == FuncIdx.end()) continue;
+        J.object([&] {
+          J.attribute("base", (int64_t)Region.first);
+          J.attribute("function", (int64_t)Func->second);
+        });
+      }
+    });
+  });
+  OKF("Source map: %s", source_map_file.c_str());
+}
+
 // Coverage statistics: AFL's Branch count
 // Angora enable function-call context.
 void AngoraLLVMPass::countEdge(Module &M, BasicBlock &BB) {
@@ -2158,6 +2286,24 @@ bool AngoraLLVMPass::runOnModule(Module &M) {
     }
   }
 
+  // Start the track binary forksrv at the entry of main
+  if (TrackMode) {
+    Function *MainFn = M.getFunction("main");
+    if (MainFn && !MainFn->isDeclaration()) {
+      IRBuilder<> IRB(&*MainFn->getEntryBlock().getFirstInsertionPt());
+      FunctionCallee TrackInitFn = M.getOrInsertFunction(
+          "__angora_track_init", FunctionType::get(VoidTy, false));
+      setInsNoSan(IRB.CreateCall(TrackInitFn));
+      // Tell the fuzzer that this binary can run under a forksrv
+      Constant *Sig =
+          ConstantDataArray::getString(M.getContext(), TRACK_FORKSRV_SIG);
+      GlobalVariable *SigVar =
+          new GlobalVariable(M, Sig->getType(), true,
+                             GlobalValue::PrivateLinkage, Sig, "__angora_sig");
+      appendToUsed(M, {SigVar});
+    }
+  }
+
   // Add the fast binary forksrv and branch counting function calls
   if (FastMode) {
     if (branchCount != 0) {
@@ -2166,6 +2312,9 @@ bool AngoraLLVMPass::runOnModule(Module &M) {
     if (getenv("PLOT_BRANCH_INSTRUMENT")) {
       plotBranchInstrument(M, "final.dot");
     }
+    if (!source_map_file.empty()) {
+      writeSourceMap();
+    }
   }
 
   if (is_bc) {
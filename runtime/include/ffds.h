@@ -2,6 +2,7 @@
 #define FFDS_H
 #include <stdint.h>
 #include <stdio.h>
+#include <sys/types.h>
 typedef uint32_t u32;
 #ifdef __cplusplus
 extern "C" {
@@ -11,6 +12,8 @@ u32 __angora_io_find_fd(int fd);
 u32 __angora_io_find_pfile(FILE *f);
 void __angora_io_add_fd(int fd);
 void __angora_io_add_pfile(FILE *f);
+void __angora_io_add_sock_fd(int fd);
+long __angora_io_sock_offset(int fd, ssize_t len);
 void __angora_io_remove_fd(int fd);
 void __angora_io_remove_pfile(FILE *f);
 
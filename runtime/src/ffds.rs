@@ -1,6 +1,9 @@
 use lazy_static::lazy_static;
 use libc;
-use std::{collections::HashSet, sync::Mutex};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
 
 lazy_static! {
     static ref FFDS: Mutex<HashSet<u32>> = {
@@ -8,6 +11,8 @@ lazy_static! {
         set.insert(libc::STDIN_FILENO as u32);
         Mutex::new(set)
     };
+    // Sockets have no file offset, so count the bytes read from them instead.
+    static ref SOCK_OFFSETS: Mutex<HashMap<u32, i64>> = Mutex::new(HashMap::new());
 }
 
 #[no_mangle]
@@ -16,6 +21,30 @@ pub extern "C" fn __angora_io_add_fd(fd: libc::c_int) {
     ffds.insert(fd as u32);
 }
 
+#[no_mangle]
+pub extern "C" fn __angora_io_add_sock_fd(fd: libc::c_int) {
+    __angora_io_add_fd(fd);
+    let mut offsets = SOCK_OFFSETS.lock().expect("Could not lock SOCK_OFFSETS.");
+    offsets.insert(fd as u32, 0);
+}
+
+/// Offset in the input of the next byte read from the socket, which then moves by `len`.
+/// Returns -1 if `fd` is not a socket we read inputs from.
+#[no_mangle]
+pub extern "C" fn __angora_io_sock_offset(fd: libc::c_int, len: libc::ssize_t) -> libc::c_long {
+    let mut offsets = SOCK_OFFSETS.lock().expect("Could not lock SOCK_OFFSETS.");
+    match offsets.get_mut(&(fd as u32)) {
+        Some(offset) => {
+            let cur = *offset;
+            if len > 0 {
+                *offset += len as i64;
+            }
+            cur as libc::c_long
+        },
+        None => -1,
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn __angora_io_add_pfile(pfile: *mut libc::FILE) {
     let fd = unsafe { libc::fileno(pfile) };
@@ -26,6 +55,8 @@ pub extern "C" fn __angora_io_add_pfile(pfile: *mut libc::FILE) {
 pub extern "C" fn __angora_io_remove_fd(fd: libc::c_int) {
     let mut ffds = FFDS.lock().expect("Could not lock FFDS.");
     ffds.remove(&(fd as u32));
+    let mut offsets = SOCK_OFFSETS.lock().expect("Could not lock SOCK_OFFSETS.");
+    offsets.remove(&(fd as u32));
 }
 
 #[no_mangle]
@@ -0,0 +1,55 @@
+// server: fuzzer/src/executor/forksrv.rs
+// The track binary runs under its own fork server, like the fast binary in runtime_fast.
+use crate::track;
+use angora_common::defs;
+use std::{env, io::prelude::*, os::unix::net::UnixStream};
+
+fn exit_forkcli(code: i32) -> ! {
+    // Skip the destructors, otherwise the fork server itself would
+    // overwrite the track log of its last child.
+    unsafe { libc::_exit(code) }
+}
+
+pub fn start_forkcli() {
+    let socket_path = match env::var(defs::FORKSRV_SOCKET_PATH_VAR) {
+        Ok(path) => path,
+        Err(_) => return,
+    };
+
+    let mut socket = match UnixStream::connect(socket_path) {
+        Ok(sock) => sock,
+        Err(e) => {
+            eprintln!("Couldn't connect: {:?}", e);
+            return;
+        },
+    };
+
+    // Tracking requests are rare, so wait for them without any timeout.
+    let mut sig_buf = [0; 4];
+    loop {
+        match socket.read(&mut sig_buf) {
+            Ok(4) => {},
+            _ => exit_forkcli(0),
+        }
+
+        let child_pid = unsafe { libc::fork() };
+        if child_pid == 0 {
+            // Each child logs to a fresh file at ANGORA_TRACK_OUTPUT.
+            track::reset_logger();
+            return;
+        }
+
+        if socket.write(&child_pid.to_le_bytes()).is_err() {
+            exit_forkcli(1);
+        }
+
+        let mut status: libc::c_int = 0;
+        if unsafe { libc::waitpid(child_pid, &mut status as *mut libc::c_int, 0) } < 0 {
+            exit_forkcli(1);
+        }
+
+        if socket.write(&status.to_le_bytes()).is_err() {
+            exit_forkcli(1);
+        }
+    }
+}
@@ -1,4 +1,5 @@
 pub mod ffds;
+pub mod forkcli;
 pub mod heapmap;
 pub mod len_label;
 pub mod logger;
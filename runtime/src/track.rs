@@ -3,7 +3,7 @@ use crate::tag_set_wrap;
 use angora_common::{cond_stmt_base::*, defs};
 use lazy_static::lazy_static;
 use libc;
-use std::{slice, sync::Mutex};
+use std::{mem, slice, sync::Mutex};
 
 // use shm_conds;
 lazy_static! {
@@ -463,6 +463,21 @@ fn log_cmp(
     }
 }
 
+// Called in a fork server child, which must not flush the logger inherited from its parent.
+pub fn reset_logger() {
+    let mut lcl = LC.lock().expect("Could not lock LC.");
+    if let Some(lc) = lcl.take() {
+        mem::forget(lc);
+    }
+    *lcl = Some(Logger::new());
+}
+
+// Inserted at the entry of main by the track mode pass.
+#[no_mangle]
+pub extern "C" fn __angora_track_init() {
+    crate::forkcli::start_forkcli();
+}
+
 #[no_mangle]
 pub extern "C" fn __angora_track_fini_rs() {
     let mut lcl = LC.lock().expect("Could not lock LC.");
@@ -4,7 +4,19 @@ use std::env;
 
 use byteorder::{LittleEndian, WriteBytesExt};
 use libc;
-use std::{io::prelude::*, os::unix::net::UnixStream, process, time::Duration};
+use std::{
+    io::prelude::*,
+    os::unix::net::UnixStream,
+    process,
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+    time::Duration,
+};
+
+// Set in the child once the fork server is up, so that persistent loops
+// only iterate when someone is there to resume them.
+static IN_FORKSRV: AtomicBool = AtomicBool::new(false);
+static FIRST_PASS: AtomicBool = AtomicBool::new(true);
+static CYCLE_CNT: AtomicU32 = AtomicU32::new(0);
 
 pub fn start_forkcli() {
     match env::var(defs::FORKSRV_SOCKET_PATH_VAR) {
@@ -18,26 +30,45 @@ pub fn start_forkcli() {
             };
 
             socket
-                .set_read_timeout(Some(Duration::from_secs(config::TIME_LIMIT_TRACK * 2)))
+                .set_read_timeout(Some(Duration::from_millis(config::TIME_LIMIT_TRACK * 2)))
                 .expect("Couldn't set read timeout");
             socket
-                .set_write_timeout(Some(Duration::from_secs(config::TIME_LIMIT_TRACK * 2)))
+                .set_write_timeout(Some(Duration::from_millis(config::TIME_LIMIT_TRACK * 2)))
                 .expect("Couldn't set write timeout");
 
             let mut sig_buf = [0; 4];
             super::shm_conds::reset_shm_conds();
 
+            // A child stopped by `__angora_persistent_loop` is waiting for its next iteration.
+            let mut child_pid: libc::pid_t = 0;
+            let mut child_stopped = false;
+
             loop {
                 if socket.read(&mut sig_buf).is_err() {
+                    if child_stopped {
+                        unsafe {
+                            libc::kill(child_pid, libc::SIGKILL);
+                            libc::waitpid(child_pid, std::ptr::null_mut(), 0);
+                        }
+                    }
                     eprintln!("exit forkcli");
                     process::exit(0);
                 }
 
-                let child_pid = unsafe { libc::fork() };
+                if child_stopped {
+                    // Resume the persistent loop instead of forking a fresh child.
+                    child_stopped = false;
+                    if unsafe { libc::kill(child_pid, libc::SIGCONT) } < 0 {
+                        process::exit(1);
+                    }
+                } else {
+                    child_pid = unsafe { libc::fork() };
 
-                if child_pid == 0 {
-                    super::shm_conds::reset_shm_conds();
-                    return;
+                    if child_pid == 0 {
+                        IN_FORKSRV.store(true, Ordering::Relaxed);
+                        super::shm_conds::reset_shm_conds();
+                        return;
+                    }
                 }
 
                 let mut pid_buf = vec![];
@@ -49,10 +80,19 @@ pub fn start_forkcli() {
                 }
 
                 let mut status: libc::c_int = 0;
-                if unsafe { libc::waitpid(child_pid, &mut status as *mut libc::c_int, 0) } < 0 {
+                if unsafe {
+                    libc::waitpid(child_pid, &mut status as *mut libc::c_int, libc::WUNTRACED)
+                } < 0
+                {
                     process::exit(1);
                 }
 
+                // The fuzzer tells a finished loop iteration apart from an exited
+                // child by `WIFSTOPPED` on the status.
+                if libc::WIFSTOPPED(status) {
+                    child_stopped = true;
+                }
+
                 let mut status_buf = vec![];
                 status_buf
                     .write_i32::<LittleEndian>(status)
@@ -67,3 +107,37 @@ pub fn start_forkcli() {
         },
     }
 }
+
+/// Backs `__ANGORA_LOOP(max_cnt)`: returns non-zero `max_cnt` times in one child,
+/// stopping itself between iterations until the fork server asks for the next run.
+/// Without a fork server, the loop body runs exactly once.
+#[no_mangle]
+pub extern "C" fn __angora_persistent_loop(max_cnt: libc::c_uint) -> libc::c_int {
+    if FIRST_PASS.swap(false, Ordering::Relaxed) {
+        // Coverage of the start-up code is not part of the first iteration.
+        if IN_FORKSRV.load(Ordering::Relaxed) {
+            super::shm_branches::clear_branch_count_table();
+        }
+        CYCLE_CNT.store(max_cnt, Ordering::Relaxed);
+        return 1;
+    }
+
+    if IN_FORKSRV.load(Ordering::Relaxed) && CYCLE_CNT.load(Ordering::Relaxed) > 1 {
+        CYCLE_CNT.fetch_sub(1, Ordering::Relaxed);
+        unsafe {
+            libc::raise(libc::SIGSTOP);
+        }
+        super::shm_branches::clear_branch_count_table();
+        super::shm_conds::reset_shm_conds_order();
+        return 1;
+    }
+
+    0
+}
+
+/// Called by the in-process worker of `libfuzzer_loader` before each test case,
+/// which starts where the last one left off instead of in a fresh child.
+#[no_mangle]
+pub extern "C" fn __angora_worker_next() {
+    super::shm_conds::reset_shm_conds_order();
+}
@@ -1,6 +1,7 @@
 pub mod fast;
 pub mod forkcli;
 pub mod shm_conds;
+pub mod shm_input;
 
 mod context;
 mod shm_branches;
@@ -2,7 +2,15 @@
 
 use angora_common::{defs::BRANCHES_SHM_ENV_VAR, shm::SharedMemory};
 use libc::{c_void, exit, off_t, size_t};
-use std::{env, os::unix::io::IntoRawFd};
+use std::{
+    env,
+    os::unix::io::IntoRawFd,
+    sync::atomic::{AtomicPtr, AtomicUsize, Ordering},
+};
+
+// Remembered so that the persistent loop can reset the table between iterations.
+static BRANCH_TABLE_PTR: AtomicPtr<c_void> = AtomicPtr::new(std::ptr::null_mut());
+static BRANCH_TABLE_SIZE: AtomicUsize = AtomicUsize::new(0);
 
 /* pub type BranchBuf = [u8; BRANCHES_SIZE];
 static mut __ANGORA_AREA_INITIAL: BranchBuf = [255; BRANCHES_SIZE]; */
@@ -54,5 +62,15 @@ pub unsafe extern "C" fn __alloc_branch_count_table(size: size_t, nmemb: off_t)
 
     shm.into_raw_fd();
 
+    BRANCH_TABLE_PTR.store(ptr, Ordering::Relaxed);
+    BRANCH_TABLE_SIZE.store(alloc_size, Ordering::Relaxed);
+
     ptr
 }
+
+pub fn clear_branch_count_table() {
+    let ptr = BRANCH_TABLE_PTR.load(Ordering::Relaxed);
+    if !ptr.is_null() {
+        unsafe { libc::memset(ptr, 0, BRANCH_TABLE_SIZE.load(Ordering::Relaxed)) };
+    }
+}
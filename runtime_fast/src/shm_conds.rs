@@ -88,6 +88,17 @@ lazy_static! {
 
 #[inline(always)]
 pub fn reset_shm_conds() {
+    reset_shm_conds_order();
+
+    unsafe {
+        context::reset_context();
+    }
+}
+
+// Unlike `reset_shm_conds`, this keeps the calling context untouched, since
+// a persistent loop restarts in the middle of the program.
+#[inline(always)]
+pub fn reset_shm_conds_order() {
     let mut conds = SHM_CONDS.lock().expect("SHM mutex poisoned.");
     match conds.deref_mut() {
         &mut Some(ref mut c) => {
@@ -95,8 +106,4 @@ pub fn reset_shm_conds() {
         },
         _ => {},
     }
-
-    unsafe {
-        context::reset_context();
-    }
 }
@@ -0,0 +1,55 @@
+// corresponding to fuzzer/src/executor/shm_input.rs
+
+use angora_common::{defs, input_buf::InputBuf, shm};
+use lazy_static::lazy_static;
+use std::{env, ptr, sync::Mutex};
+
+pub struct ShmInput {
+    input: shm::SHM<InputBuf>,
+}
+
+// shm contains pointer..
+unsafe impl Send for ShmInput {}
+
+impl ShmInput {
+    pub fn get_from_env_id() -> Option<Self> {
+        let id_val = env::var(defs::INPUT_SHM_ENV_VAR);
+        match id_val {
+            Ok(val) => {
+                let shm_id = val.parse::<i32>().expect("Could not parse i32 value.");
+                let input = shm::SHM::<InputBuf>::from_id(shm_id);
+                if input.is_fail() {
+                    None
+                } else {
+                    Some(Self { input })
+                }
+            },
+            Err(_) => None,
+        }
+    }
+}
+
+lazy_static! {
+    static ref SHM_INPUT: Mutex<Option<ShmInput>> = Mutex::new(ShmInput::get_from_env_id());
+}
+
+/// Backs `__ANGORA_FUZZ_TESTCASE_BUF`. Null if the fuzzer does not deliver
+/// test cases through shared memory, in which case the harness reads the input file.
+#[no_mangle]
+pub extern "C" fn __angora_fuzz_testcase_buf() -> *const u8 {
+    let input = SHM_INPUT.lock().expect("SHM mutex poisoned.");
+    match input.as_ref() {
+        Some(i) => i.input.as_slice().as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Backs `__ANGORA_FUZZ_TESTCASE_LEN`, -1 if there is no shared memory test case.
+#[no_mangle]
+pub extern "C" fn __angora_fuzz_testcase_len() -> libc::ssize_t {
+    let input = SHM_INPUT.lock().expect("SHM mutex poisoned.");
+    match input.as_ref() {
+        Some(i) => i.input.as_slice().len() as libc::ssize_t,
+        None => -1,
+    }
+}
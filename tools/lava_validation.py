@@ -24,9 +24,11 @@ def locate_crashes(crash_dirs, prom_bin, flags, save_dir):
     bugs_id = {}                                             
     for cur_dir in crash_dirs:
         is_crash_dir = cur_dir.endswith("crashes/")
-        for file in os.listdir(cur_dir):
-            if (file != "README.txt"):                
-                cur_file = cur_dir + file
+        # crashes/ has a directory for each bucket
+        files = [os.path.join(d, f) for d, _, fs in os.walk(cur_dir) for f in fs]
+        for cur_file in files:
+            file = os.path.basename(cur_file)
+            if file.startswith("id:") and "." not in file:
                 cmd = [prom_bin]            
                 for flag in flags:
                     cmd.append(flag)